@@ -4,7 +4,7 @@ use hmac::{Hmac, Mac};
 use sha2::{Sha256, Sha512};
 use sthash::*;
 
-fn hash(hasher: &Hasher, msg: &[u8]) -> Vec<u8> {
+fn hash(hasher: &Hasher, msg: &[u8]) -> Output {
     hasher.hash(msg)
 }
 
@@ -36,6 +36,17 @@ fn hash_sha256(msg: &[u8]) -> Vec<u8> {
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
+    for &(len, name) in &[(64, "64 o"), (1024, "1 Ko"), (4096, "4 Ko")] {
+        c.bench_function(&format!("STHash {}", name), |b| {
+            let seed = [0x42; SEED_BYTES];
+            let key = Key::from_seed(&seed, Some(b"test suite"));
+            let hasher = Hasher::new(key, None);
+
+            let msg = vec![0x69; len];
+            b.iter(|| hash(&hasher, &msg))
+        });
+    }
+
     c.bench_function("STHash 1 Mo", |b| {
         let seed = [0x42; SEED_BYTES];
         let key = Key::from_seed(&seed, Some(b"test suite"));
@@ -0,0 +1,154 @@
+use core::future::{poll_fn, Future};
+use core::pin::Pin;
+use core::task::{ready, Context, Poll};
+use std::io;
+
+use futures_core::Stream;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+use tokio::task::{self, JoinError, JoinHandle};
+
+use super::output::Output;
+use super::sthash::*;
+
+/// Chunks of at least this size are hashed on a blocking thread, in bytes
+pub const ASYNC_BLOCKING_BYTES: usize = 1024 * 1024;
+
+/// Returns the result of a blocking task, propagating its panics
+fn join_result<T>(res: Result<T, JoinError>) -> T {
+    res.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+}
+
+/// Adds `data` to `st`, on a blocking thread if `data` is large
+async fn update_offloaded<T>(mut st: State, data: T) -> (State, T)
+where
+    T: AsRef<[u8]> + Send + 'static,
+{
+    if data.as_ref().len() < ASYNC_BLOCKING_BYTES {
+        st.update(data.as_ref());
+        return (st, data);
+    }
+    join_result(
+        task::spawn_blocking(move || {
+            st.update(data.as_ref());
+            (st, data)
+        })
+        .await,
+    )
+}
+
+impl Hasher {
+    /// Returns an `OUTPUT_BYTES` hash of all the data that can be read from `reader`
+    ///
+    /// Data is read into a buffer of `ASYNC_BLOCKING_BYTES` bytes. Full
+    /// buffers are hashed on a blocking thread, so that hashing large
+    /// streams doesn't block the executor.
+    pub async fn hash_async_reader<R: AsyncRead + Unpin>(
+        &self,
+        mut reader: R,
+    ) -> io::Result<Output> {
+        let mut st = self.start();
+        let mut buf = vec![0u8; ASYNC_BLOCKING_BYTES];
+        loop {
+            let mut len = 0;
+            while len < buf.len() {
+                match reader.read(&mut buf[len..]).await {
+                    Ok(0) => break,
+                    Ok(n) => len += n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+            if len < buf.len() {
+                st.update(&buf[..len]);
+                return Ok(st.finalize());
+            }
+            (st, buf) = update_offloaded(st, buf).await;
+        }
+    }
+
+    /// Returns an `OUTPUT_BYTES` hash of the concatenation of the chunks
+    /// produced by `stream`
+    ///
+    /// Chunks of at least `ASYNC_BLOCKING_BYTES` bytes are hashed on a
+    /// blocking thread. The first error produced by the stream is returned.
+    pub async fn hash_stream<S, B, E>(&self, mut stream: S) -> Result<Output, E>
+    where
+        S: Stream<Item = Result<B, E>> + Unpin,
+        B: AsRef<[u8]> + Send + 'static,
+    {
+        let mut st = self.start();
+        while let Some(chunk) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            st = update_offloaded(st, chunk?).await.0;
+        }
+        Ok(st.finalize())
+    }
+
+    /// Returns a new incremental hashing state, that implements `AsyncWrite`
+    pub fn start_async(&self) -> AsyncState {
+        AsyncState {
+            st: Some(self.start()),
+            task: None,
+        }
+    }
+}
+
+/// An incremental hashing state that implements `AsyncWrite`, created with
+/// `Hasher::start_async()`
+///
+/// Writes of at least `ASYNC_BLOCKING_BYTES` bytes are copied, and hashed
+/// on a blocking thread. Such writes complete immediately, and the next
+/// write, flush or `finalize()` waits for the data to be hashed.
+/// Writes never fail.
+pub struct AsyncState {
+    st: Option<State>,
+    task: Option<JoinHandle<State>>,
+}
+
+impl AsyncState {
+    /// Waits for the data written by a previous write to be hashed
+    fn poll_task(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(task) = self.task.as_mut() {
+            let st = join_result(ready!(Pin::new(task).poll(cx)));
+            self.st = Some(st);
+            self.task = None;
+        }
+        Poll::Ready(())
+    }
+
+    /// Returns an `OUTPUT_BYTES` hash of all the data written to the state
+    pub async fn finalize(mut self) -> Output {
+        poll_fn(|cx| self.poll_task(cx)).await;
+        self.st.take().unwrap().finalize()
+    }
+}
+
+impl AsyncWrite for AsyncState {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_task(cx));
+        let mut st = this.st.take().unwrap();
+        if buf.len() < ASYNC_BLOCKING_BYTES {
+            st.update(buf);
+            this.st = Some(st);
+        } else {
+            let buf = buf.to_vec();
+            this.task = Some(task::spawn_blocking(move || {
+                st.update(&buf);
+                st
+            }));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_task(cx).map(Ok)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_task(cx).map(Ok)
+    }
+}
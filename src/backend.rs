@@ -0,0 +1,121 @@
+use core::fmt;
+use core::str::FromStr;
+
+use super::error::Error;
+
+/// Name of the environment variable that can force the backend used by `Hasher::new()`
+pub const BACKEND_ENV_VAR: &str = "STHASH_BACKEND";
+
+/// An implementation of the NH compression function
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Backend {
+    /// Portable implementation, available on all platforms
+    Portable,
+    /// SSE2 implementation, for x86_64 CPUs
+    Sse2,
+    /// AVX2 implementation of NH and Poly1305, for x86_64 CPUs
+    Avx2,
+}
+
+impl Backend {
+    /// All the backends, from the slowest to the fastest
+    pub const ALL: &'static [Backend] = &[Backend::Portable, Backend::Sse2, Backend::Avx2];
+
+    /// Returns the name of the backend
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Portable => "portable",
+            Backend::Sse2 => "sse2",
+            Backend::Avx2 => "avx2",
+        }
+    }
+
+    /// Returns `true` if the backend can be used on the current CPU
+    ///
+    /// Without the `std` feature, CPU features are not detected at runtime,
+    /// and SIMD backends are only supported if they were enabled at compile time.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Backend::Portable => true,
+            Backend::Sse2 => cfg!(target_arch = "x86_64"),
+            Backend::Avx2 => {
+                #[cfg(all(target_arch = "x86_64", feature = "std"))]
+                {
+                    is_x86_feature_detected!("avx2")
+                }
+                #[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+                {
+                    cfg!(target_feature = "avx2")
+                }
+                #[cfg(not(target_arch = "x86_64"))]
+                {
+                    false
+                }
+            }
+        }
+    }
+
+    /// Returns the backend used by default by `Hasher::new()`
+    ///
+    /// This is the fastest supported backend, unless the `STHASH_BACKEND`
+    /// environment variable is set to the name of another backend.
+    /// See `default_backend_from()` for how the variable is interpreted.
+    pub(crate) fn default_backend() -> Result<Backend, Error> {
+        #[cfg(feature = "std")]
+        {
+            if let Some(value) = std::env::var_os(BACKEND_ENV_VAR) {
+                let value = value.to_str().ok_or(Error::UnsupportedBackend)?;
+                return Backend::default_backend_from(Some(value));
+            }
+        }
+        Backend::default_backend_from(None)
+    }
+
+    /// Returns the default backend, given the value of the `STHASH_BACKEND`
+    /// environment variable
+    ///
+    /// Without a value, or with an empty value, this is the fastest supported
+    /// backend. Since the variable is meant to reproduce bugs with a specific
+    /// backend, an unknown backend, or a backend that is not supported by the
+    /// CPU, is an error rather than being silently replaced.
+    pub(crate) fn default_backend_from(value: Option<&str>) -> Result<Backend, Error> {
+        match value.map(str::trim) {
+            None | Some("") => Ok(detected_backend()),
+            Some(name) => name
+                .parse::<Backend>()
+                .ok()
+                .filter(|backend| backend.is_supported())
+                .ok_or(Error::UnsupportedBackend),
+        }
+    }
+}
+
+/// Returns the fastest backend supported by the current CPU
+pub fn detected_backend() -> Backend {
+    Backend::ALL
+        .iter()
+        .rev()
+        .copied()
+        .find(|backend| backend.is_supported())
+        .unwrap_or(Backend::Portable)
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parses a backend name, case-insensitively
+impl FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Backend, Error> {
+        Backend::ALL
+            .iter()
+            .copied()
+            .find(|backend| backend.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or(Error::UnsupportedBackend)
+    }
+}
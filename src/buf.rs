@@ -0,0 +1,29 @@
+use bytes::Buf;
+
+use super::output::Output;
+use super::sthash::*;
+
+impl Hasher {
+    /// Returns an `OUTPUT_BYTES` hash of the remaining content of `buf`
+    ///
+    /// Non-contiguous buffers, such as chained `Bytes`, are hashed chunk by
+    /// chunk, without being copied into a contiguous buffer first.
+    pub fn hash_buf<B: Buf>(&self, buf: B) -> Output {
+        let mut st = self.start();
+        st.update_buf(buf);
+        st.finalize()
+    }
+}
+
+impl State {
+    /// Adds the remaining content of `buf` to the data to be hashed,
+    /// consuming it
+    pub fn update_buf<B: Buf>(&mut self, mut buf: B) {
+        while buf.has_remaining() {
+            let chunk = buf.chunk();
+            let len = chunk.len();
+            self.update(chunk);
+            buf.advance(len);
+        }
+    }
+}
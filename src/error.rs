@@ -0,0 +1,50 @@
+use core::fmt;
+
+/// Errors returned by fallible STHash operations
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The input is not a valid encoding of the expected value
+    InvalidEncoding,
+    /// The seed is shorter than `MIN_SEED_BYTES`
+    SeedTooShort,
+    /// The seed is obviously not random
+    WeakSeed,
+    /// The key doesn't have the expected length
+    InvalidKeyLength,
+    /// The key file or checkpoint was created by an unsupported version of its format
+    UnsupportedVersion,
+    /// The key file checksum doesn't match its content
+    ChecksumMismatch,
+    /// The backend is unknown, or not supported by the current CPU
+    UnsupportedBackend,
+    /// A partial hash that doesn't end on a block boundary was followed by more data
+    UnalignedPartial,
+    /// The checkpoint was modified, or created with a different key
+    InvalidCheckpoint,
+    /// The length of the message doesn't fit in 64 bits
+    MessageTooLong,
+    /// The personalization string is too long to be stored in a key file
+    PersonalizationTooLong,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidEncoding => write!(f, "Invalid encoding"),
+            Error::SeedTooShort => write!(f, "Seed is too short"),
+            Error::WeakSeed => write!(f, "Seed is not random"),
+            Error::InvalidKeyLength => write!(f, "Invalid key length"),
+            Error::UnsupportedVersion => write!(f, "Unsupported format version"),
+            Error::ChecksumMismatch => write!(f, "Key file checksum mismatch"),
+            Error::UnsupportedBackend => write!(f, "Unsupported backend"),
+            Error::UnalignedPartial => write!(f, "Partial hash is not aligned to a block boundary"),
+            Error::InvalidCheckpoint => write!(f, "Checkpoint authentication failed"),
+            Error::MessageTooLong => write!(f, "Message is too long"),
+            Error::PersonalizationTooLong => write!(f, "Personalization string is too long"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
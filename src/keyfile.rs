@@ -0,0 +1,261 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::{fs, io, path::Path};
+
+use byteorder::{ByteOrder, LittleEndian};
+use tiny_keccak::{CShake, Hasher as _};
+
+use super::error::Error;
+use super::sthash::*;
+
+const KEY_FILE_MAGIC: &[u8; 4] = b"STHK";
+const KEY_FILE_VERSION: u8 = 1;
+const KEY_FILE_CHECKSUM_BYTES: usize = 16;
+const KEY_FILE_HEADER_BYTES: usize = 4 + 1 + 1 + 2;
+/// Maximum length of the personalization string of a key file, in bytes
+pub const KEY_FILE_MAX_PERSONALIZATION_BYTES: usize = u16::MAX as usize;
+
+const ARMOR_BEGIN: &str = "-----BEGIN STHASH KEY-----";
+const ARMOR_END: &str = "-----END STHASH KEY-----";
+const ARMOR_LINE_LENGTH: usize = 64;
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A temporary buffer holding key material, wiped when dropped with the
+/// `zeroize` feature
+#[cfg(feature = "zeroize")]
+type Secret<T> = zeroize::Zeroizing<T>;
+
+#[cfg(not(feature = "zeroize"))]
+type Secret<T> = T;
+
+#[cfg(feature = "zeroize")]
+fn secret<T: zeroize::Zeroize>(t: T) -> Secret<T> {
+    zeroize::Zeroizing::new(t)
+}
+
+#[cfg(not(feature = "zeroize"))]
+fn secret<T>(t: T) -> Secret<T> {
+    t
+}
+
+/// A key, along with the personalization string that was used to derive it
+/// from a seed
+///
+/// A `KeyFile` can be serialized to a versioned, checksummed binary format,
+/// or to an ASCII-armored text format, so that keys can be shared between
+/// services.
+///
+/// The binary format is:
+///
+/// ```text
+/// "STHK" || version (1) || has_personalization (1) || le16(|personalization|) ||
+/// personalization || key || checksum
+/// ```
+///
+/// where `checksum` is a 128-bit cSHAKE128 hash of the previous fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyFile {
+    key: Key,
+    personalization: Option<Vec<u8>>,
+}
+
+impl KeyFile {
+    /// Creates a `KeyFile` for `key`, that was derived using `personalization`
+    ///
+    /// An error is returned if `personalization` is longer than
+    /// `KEY_FILE_MAX_PERSONALIZATION_BYTES`.
+    pub fn new(key: Key, personalization: Option<&[u8]>) -> Result<KeyFile, Error> {
+        if personalization.is_some_and(|p| p.len() > KEY_FILE_MAX_PERSONALIZATION_BYTES) {
+            return Err(Error::PersonalizationTooLong);
+        }
+        Ok(KeyFile {
+            key,
+            personalization: personalization.map(|p| p.to_vec()),
+        })
+    }
+
+    /// Derives a key from a secret `seed`, and records the personalization string
+    ///
+    /// An error is returned if the seed is rejected by `Key::try_from_seed()`,
+    /// or if the personalization string is too long.
+    pub fn from_seed(seed: &[u8], personalization: Option<&[u8]>) -> Result<KeyFile, Error> {
+        KeyFile::new(Key::try_from_seed(seed, personalization)?, personalization)
+    }
+
+    /// Returns the key
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    /// Returns the key, consuming the `KeyFile`
+    pub fn into_key(self) -> Key {
+        self.key
+    }
+
+    /// Returns the personalization string used to derive the key
+    pub fn personalization(&self) -> Option<&[u8]> {
+        self.personalization.as_deref()
+    }
+
+    /// Returns the binary representation of the key file
+    ///
+    /// The returned buffer contains the key, and is owned by the caller:
+    /// it is not wiped from memory, even with the `zeroize` feature.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let personalization = self.personalization().unwrap_or_default();
+        debug_assert!(personalization.len() <= KEY_FILE_MAX_PERSONALIZATION_BYTES);
+        let mut bytes = Vec::with_capacity(
+            KEY_FILE_HEADER_BYTES + personalization.len() + KEY_BYTES + KEY_FILE_CHECKSUM_BYTES,
+        );
+        bytes.extend_from_slice(KEY_FILE_MAGIC);
+        bytes.push(KEY_FILE_VERSION);
+        bytes.push(self.personalization.is_some() as u8);
+        let mut personalization_len = [0u8; 2];
+        LittleEndian::write_u16(&mut personalization_len, personalization.len() as u16);
+        bytes.extend_from_slice(&personalization_len);
+        bytes.extend_from_slice(personalization);
+        bytes.extend_from_slice(self.key.as_bytes());
+        let checksum = checksum(&bytes);
+        bytes.extend_from_slice(&checksum);
+        bytes
+    }
+
+    /// Parses the binary representation of a key file
+    pub fn from_bytes(bytes: &[u8]) -> Result<KeyFile, Error> {
+        if bytes.len() < KEY_FILE_HEADER_BYTES + KEY_FILE_CHECKSUM_BYTES
+            || &bytes[0..4] != KEY_FILE_MAGIC
+        {
+            return Err(Error::InvalidEncoding);
+        }
+        if bytes[4] != KEY_FILE_VERSION {
+            return Err(Error::UnsupportedVersion);
+        }
+        let (content, expected_checksum) = bytes.split_at(bytes.len() - KEY_FILE_CHECKSUM_BYTES);
+        if checksum(content) != expected_checksum {
+            return Err(Error::ChecksumMismatch);
+        }
+        let has_personalization = match bytes[5] {
+            0 => false,
+            1 => true,
+            _ => return Err(Error::InvalidEncoding),
+        };
+        let personalization_len = LittleEndian::read_u16(&bytes[6..8]) as usize;
+        if !has_personalization && personalization_len != 0 {
+            return Err(Error::InvalidEncoding);
+        }
+        let content = &content[KEY_FILE_HEADER_BYTES..];
+        if content.len() < personalization_len {
+            return Err(Error::InvalidEncoding);
+        }
+        let (personalization, key) = content.split_at(personalization_len);
+        let key = Key::from_bytes(key)?;
+        Ok(KeyFile {
+            key,
+            personalization: if has_personalization {
+                Some(personalization.to_vec())
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Returns the ASCII-armored representation of the key file
+    ///
+    /// Like the output of `to_bytes()`, the returned string contains the key,
+    /// and is owned by the caller. Intermediate buffers are wiped with the
+    /// `zeroize` feature.
+    pub fn to_armored(&self) -> String {
+        let bytes = secret(self.to_bytes());
+        let encoded = secret(base64_encode(&bytes));
+        let mut armored = String::with_capacity(encoded.len() * 65 / 64 + 64);
+        armored.push_str(ARMOR_BEGIN);
+        armored.push('\n');
+        for line in encoded.as_bytes().chunks(ARMOR_LINE_LENGTH) {
+            armored.push_str(core::str::from_utf8(line).unwrap());
+            armored.push('\n');
+        }
+        armored.push_str(ARMOR_END);
+        armored.push('\n');
+        armored
+    }
+
+    /// Parses the ASCII-armored representation of a key file
+    pub fn from_armored(armored: &str) -> Result<KeyFile, Error> {
+        let armored = armored.trim();
+        let encoded = armored
+            .strip_prefix(ARMOR_BEGIN)
+            .and_then(|s| s.strip_suffix(ARMOR_END))
+            .ok_or(Error::InvalidEncoding)?;
+        // Allocated once, so that no copies of the key are left behind
+        let mut encoded_bin = secret(Vec::with_capacity(encoded.len()));
+        encoded_bin.extend(encoded.bytes().filter(|c| !c.is_ascii_whitespace()));
+        let bytes = secret(base64_decode(&encoded_bin)?);
+        KeyFile::from_bytes(&bytes)
+    }
+
+    /// Loads a key file, in binary or ASCII-armored format
+    #[cfg(feature = "std")]
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<KeyFile> {
+        let bytes = secret(fs::read(path)?);
+        let key_file = if bytes.starts_with(KEY_FILE_MAGIC) {
+            KeyFile::from_bytes(&bytes)
+        } else {
+            core::str::from_utf8(&bytes)
+                .map_err(|_| Error::InvalidEncoding)
+                .and_then(KeyFile::from_armored)
+        };
+        key_file.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+fn checksum(content: &[u8]) -> [u8; KEY_FILE_CHECKSUM_BYTES] {
+    let mut st_cshake = CShake::v128(b"sthash key file", &[]);
+    st_cshake.update(content);
+    let mut checksum = [0u8; KEY_FILE_CHECKSUM_BYTES];
+    st_cshake.finalize(&mut checksum);
+    checksum
+}
+
+fn base64_encode(bin: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bin.len().div_ceil(3) * 4);
+    for chunk in bin.chunks(3) {
+        let mut t = [0u8; 3];
+        t[..chunk.len()].copy_from_slice(chunk);
+        let n = (t[0] as u32) << 16 | (t[1] as u32) << 8 | t[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn base64_decode(encoded: &[u8]) -> Result<Vec<u8>, Error> {
+    if !encoded.len().is_multiple_of(4) {
+        return Err(Error::InvalidEncoding);
+    }
+    let mut bin = Vec::with_capacity(encoded.len() / 4 * 3);
+    let chunks_count = encoded.len() / 4;
+    for (i, chunk) in encoded.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && i != chunks_count - 1) {
+            return Err(Error::InvalidEncoding);
+        }
+        let mut n = 0u32;
+        for &c in &chunk[..4 - padding] {
+            let v = BASE64_ALPHABET
+                .iter()
+                .position(|&x| x == c)
+                .ok_or(Error::InvalidEncoding)?;
+            n = n << 6 | v as u32;
+        }
+        n <<= 6 * padding;
+        bin.extend_from_slice(&[(n >> 16) as u8, (n >> 8) as u8, n as u8][..3 - padding]);
+    }
+    Ok(bin)
+}
@@ -1,12 +1,40 @@
 #![doc = include_str!("../README.md")]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![allow(clippy::cast_lossless)]
 #![allow(clippy::unreadable_literal)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "tokio")]
+mod async_io;
+mod backend;
+#[cfg(feature = "bytes")]
+mod buf;
+mod error;
+#[cfg(feature = "alloc")]
+mod keyfile;
 mod nhpoly1305;
+mod output;
+#[cfg(feature = "rayon")]
+mod parallel;
 mod poly1305;
+#[cfg(feature = "std")]
+mod reader;
 mod sthash;
 
 #[cfg(test)]
 mod test;
 
+#[cfg(feature = "tokio")]
+pub use crate::async_io::*;
+pub use crate::backend::*;
+pub use crate::error::*;
+#[cfg(feature = "alloc")]
+pub use crate::keyfile::*;
+pub use crate::output::*;
+#[cfg(feature = "rayon")]
+pub use crate::parallel::*;
+#[cfg(feature = "std")]
+pub use crate::reader::*;
 pub use crate::sthash::*;
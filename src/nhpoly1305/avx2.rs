@@ -1,38 +1,90 @@
 use super::*;
 
-impl Hasher<'_> {
+impl State {
     #[cfg(target_arch = "x86_64")]
     #[target_feature(enable = "avx2")]
-    pub(crate) unsafe fn hash_avx2(&self, out: &mut [u8; 16], msg: &[u8]) {
-        let key = self.key;
-        let mut poly_key = [0u8; 16];
-        poly_key.copy_from_slice(&key[0..16]);
-        let nh_key = &key[16..];
-        let mut nh_out = vec![];
-        let mut remaining = msg.len();
-        let mut off = 0;
-        let mut st_poly = poly1305::Hasher::new(poly_key);
-        let st_nh = nh::Hasher::new(nh_key);
-        while remaining > nh::NH_MESSAGE_BYTES {
-            st_nh.hash_avx2(&mut nh_out, &msg[off..off + nh::NH_MESSAGE_BYTES]);
-            if nh_out.len() == nh::NH_OUTPUT_BYTES * NHPOLY_HASHES_PER_POLY {
-                st_poly.update(&nh_out);
-                nh_out.truncate(0);
+    pub(crate) unsafe fn update_blocks_avx2(&mut self, hasher: &Hasher, blocks: &[u8]) {
+        for block in blocks.chunks_exact(nh::NH_MESSAGE_BYTES) {
+            hasher.st_nh.hash_avx2(self.nh_out_slot(), block);
+            if self.push_nh_out() {
+                self.st_poly.update_avx2(&hasher.poly_powers, &self.nh_out);
+                self.nh_out_len = 0;
             }
-            off += nh::NH_MESSAGE_BYTES;
-            remaining -= nh::NH_MESSAGE_BYTES;
         }
-        if remaining > 0 {
-            let mask = nh::NH_MESSAGE_UNIT - 1;
-            let padded_len = (remaining + mask) & !mask;
-            let mut unit = [0u8; nh::NH_MESSAGE_BYTES];
-            let padded = &mut unit[..padded_len];
-            padded[..remaining].copy_from_slice(&msg[off..]);
-            st_nh.hash(&mut nh_out, &padded);
+    }
+}
+
+impl Hasher {
+    /// Hashes up to 4 independent messages, one per Poly1305 lane
+    ///
+    /// Messages are aligned by their last NH block: lanes of shorter messages
+    /// stay inactive until their first block.
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    pub(crate) unsafe fn hash_lanes_avx2(&self, msgs: &[&[u8]], outs: &mut [[u8; 16]]) {
+        debug_assert!(msgs.len() <= 4);
+        let mut counts = [0usize; 4];
+        for (count, msg) in counts.iter_mut().zip(msgs.iter()) {
+            *count = msg.len().div_ceil(nh::NH_MESSAGE_BYTES);
         }
-        if !nh_out.is_empty() {
-            st_poly.update(&nh_out);
+        let max_count = counts.iter().copied().max().unwrap_or(0);
+        let mut lanes = poly1305::Lanes::new(&self.poly_key, &self.poly_powers);
+        for i in 0..max_count {
+            let mut blocks: [Option<&[u8]>; 4] = [None; 4];
+            for (j, msg) in msgs.iter().enumerate() {
+                if i + counts[j] >= max_count {
+                    let start = (i + counts[j] - max_count) * nh::NH_MESSAGE_BYTES;
+                    let end = msg.len().min(start + nh::NH_MESSAGE_BYTES);
+                    blocks[j] = Some(&msg[start..end]);
+                }
+            }
+
+            let mut nh_outs = [[0u8; nh::NH_OUTPUT_BYTES]; 4];
+            match blocks {
+                [Some(b0), Some(b1), Some(b2), Some(b3)]
+                    if [b0, b1, b2, b3]
+                        .iter()
+                        .all(|block| block.len() == nh::NH_MESSAGE_BYTES) =>
+                {
+                    self.st_nh.hash_avx2_x4(&mut nh_outs, [b0, b1, b2, b3]);
+                }
+                _ => {
+                    for (nh_out, block) in nh_outs.iter_mut().zip(blocks.iter()) {
+                        match block {
+                            Some(block) if block.len() == nh::NH_MESSAGE_BYTES => {
+                                self.st_nh.hash_avx2(nh_out, block)
+                            }
+                            Some(block) => {
+                                // Last partial block, padded like in `State::finalize()`
+                                let mut padded = [0u8; nh::NH_MESSAGE_BYTES];
+                                let padded_len =
+                                    block.len().div_ceil(nh::NH_MESSAGE_UNIT) * nh::NH_MESSAGE_UNIT;
+                                padded[..block.len()].copy_from_slice(block);
+                                self.st_nh.hash(nh_out, &padded[..padded_len]);
+                            }
+                            None => {}
+                        }
+                    }
+                }
+            }
+
+            // Each NH output is made of two Poly1305 blocks
+            let active = [
+                blocks[0].is_some(),
+                blocks[1].is_some(),
+                blocks[2].is_some(),
+                blocks[3].is_some(),
+            ];
+            let (mut lo, mut hi) = ([0u8; 64], [0u8; 64]);
+            for (j, nh_out) in nh_outs.iter().enumerate() {
+                lo[j * 16..][..16].copy_from_slice(&nh_out[..16]);
+                hi[j * 16..][..16].copy_from_slice(&nh_out[16..]);
+            }
+            lanes.update(&lo, active);
+            lanes.update(&hi, active);
         }
-        st_poly.finalize_noadd(out);
+        let mut polys = [[0u8; 16]; 4];
+        lanes.finalize_noadd(&mut polys);
+        outs.copy_from_slice(&polys[..outs.len()]);
     }
 }
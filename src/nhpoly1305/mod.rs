@@ -1,30 +1,328 @@
+use core::convert::TryInto;
+
+use super::backend::Backend;
 use super::poly1305;
 
 mod avx2;
 mod nh;
 mod portable;
+mod sse2;
 
 pub const NHPOLY_KEY_BYTES: usize = poly1305::POLY_KEY_BYTES + nh::NH_KEY_BYTES_PER_MESSAGE;
 
+pub const NHPOLY_BLOCK_BYTES: usize = nh::NH_MESSAGE_BYTES;
+
+pub const NHPOLY_ACCUMULATOR_BYTES: usize = poly1305::ACCUMULATOR_BYTES;
+
 const NHPOLY_HASHES_PER_POLY: usize = 16; // 16 * (4 * u64 sums) polys over 512 bytes
+const NHPOLY_NH_OUT_BYTES: usize = nh::NH_OUTPUT_BYTES * NHPOLY_HASHES_PER_POLY;
+
+type UpdateBlocksFn = unsafe fn(&mut State, &Hasher, &[u8]);
+type UpdatePolyFn = unsafe fn(&mut poly1305::Hasher, &[u8]);
 
-pub struct Hasher<'t> {
-    key: &'t [u8],
+/// NH and Poly1305 keys, expanded once and shared by all the messages,
+/// and the NH implementation to use
+#[derive(Clone)]
+pub struct Hasher {
+    poly_key: poly1305::Key,
+    #[cfg(target_arch = "x86_64")]
+    poly_powers: poly1305::Powers,
+    st_nh: nh::Hasher,
+    backend: Backend,
+    update_blocks: UpdateBlocksFn,
+    update_poly: UpdatePolyFn,
 }
 
-impl Hasher<'_> {
-    pub fn hash(&self, out: &mut [u8; 16], msg: &[u8]) {
+impl Hasher {
+    pub fn start(&self) -> State {
+        State {
+            st_poly: poly1305::Hasher::new(self.poly_key.clone()),
+            nh_out: [0u8; NHPOLY_NH_OUT_BYTES],
+            nh_out_len: 0,
+            pending: [0u8; nh::NH_MESSAGE_BYTES],
+            pending_len: 0,
+        }
+    }
+
+    /// Returns a state that continues from previously exported values
+    ///
+    /// `None` is returned if `nh_out` is not a sequence of NH outputs shorter
+    /// than a full batch, or if `pending` is not shorter than a block.
+    #[cfg(feature = "alloc")]
+    pub fn resume(&self, poly_limbs: [u64; 3], nh_out: &[u8], pending: &[u8]) -> Option<State> {
+        if nh_out.len() >= NHPOLY_NH_OUT_BYTES
+            || !nh_out.len().is_multiple_of(nh::NH_OUTPUT_BYTES)
+            || pending.len() >= nh::NH_MESSAGE_BYTES
+        {
+            return None;
+        }
+        let mut st = self.start();
+        st.st_poly = poly1305::Hasher::with_limbs(self.poly_key.clone(), poly_limbs);
+        st.nh_out[..nh_out.len()].copy_from_slice(nh_out);
+        st.nh_out_len = nh_out.len();
+        st.pending[..pending.len()].copy_from_slice(pending);
+        st.pending_len = pending.len();
+        Some(st)
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Computes the hash of `msg` using up to `threads` threads
+    ///
+    /// The message is split into ranges of full NH blocks. Since Poly1305
+    /// evaluates a polynomial in `r`, the accumulator of each range can be
+    /// computed independently, and then combined with powers of `r`.
+    #[cfg(feature = "std")]
+    pub fn hash_parallel(&self, msg: &[u8], threads: usize, out: &mut [u8; 16]) {
+        let blocks = msg.len().div_ceil(nh::NH_MESSAGE_BYTES);
+        let threads = threads.clamp(1, blocks.max(1));
+        let range_bytes = blocks.div_ceil(threads) * nh::NH_MESSAGE_BYTES;
+        let hash_range = |range: &[u8]| {
+            let mut st = self.start();
+            st.update(self, range);
+            st.flush(self);
+            st
+        };
+        if threads == 1 {
+            return hash_range(msg).st_poly.finalize_noadd(out);
+        }
+        let mut ranges = msg.chunks(range_bytes);
+        let first_range = ranges.next().unwrap();
+        let (mut st, sts) = std::thread::scope(|scope| {
+            let handles: Vec<_> = ranges
+                .clone()
+                .map(|range| scope.spawn(move || hash_range(range)))
+                .collect();
+            let st = hash_range(first_range);
+            let sts: Vec<_> = handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect();
+            (st, sts)
+        });
+        for (range, range_st) in ranges.zip(sts.iter()) {
+            let poly_blocks = range.len().div_ceil(nh::NH_MESSAGE_BYTES) * nh::NH_OUTPUT_BYTES / 16;
+            st.st_poly.combine(&range_st.st_poly, poly_blocks as u64);
+        }
+        st.st_poly.finalize_noadd(out);
+    }
+
+    /// Computes the Poly1305 accumulator of `msg`
+    ///
+    /// Accumulators of consecutive ranges can be combined with
+    /// `concat_accumulators()`, as long as all the ranges but the last one
+    /// are made of full NH blocks.
+    pub fn hash_accumulator(&self, msg: &[u8], acc: &mut [u8; NHPOLY_ACCUMULATOR_BYTES]) {
+        let mut st = self.start();
+        st.update(self, msg);
+        st.flush(self);
+        *acc = st.st_poly.accumulator();
+    }
+
+    /// Replaces `acc` with the accumulator of its range followed by the range
+    /// of `other`, made of `other_blocks` NH blocks
+    pub fn concat_accumulators(
+        &self,
+        acc: &mut [u8; NHPOLY_ACCUMULATOR_BYTES],
+        other: &[u8; NHPOLY_ACCUMULATOR_BYTES],
+        other_blocks: u64,
+    ) {
+        let mut st = poly1305::Hasher::with_accumulator(self.poly_key.clone(), acc);
+        let other = poly1305::Hasher::with_accumulator(self.poly_key.clone(), other);
+        let poly_blocks = other_blocks * (nh::NH_OUTPUT_BYTES / 16) as u64;
+        st.combine(&other, poly_blocks);
+        *acc = st.accumulator();
+    }
+
+    /// Computes the hash of the range whose accumulator is `acc`
+    pub fn finalize_accumulator(&self, acc: &[u8; NHPOLY_ACCUMULATOR_BYTES], out: &mut [u8; 16]) {
+        poly1305::Hasher::with_accumulator(self.poly_key.clone(), acc).finalize_noadd(out);
+    }
+
+    /// Computes the hashes of independent messages
+    ///
+    /// The result is the same as hashing each message with `start()`,
+    /// `update()` and `finalize()`.
+    pub fn hash_batch(&self, msgs: &[&[u8]], outs: &mut [[u8; 16]]) {
+        debug_assert_eq!(msgs.len(), outs.len());
         #[cfg(target_arch = "x86_64")]
         {
-            if is_x86_feature_detected!("avx2") {
-                return unsafe { self.hash_avx2(out, msg) };
+            if self.backend == Backend::Avx2 {
+                for (msgs, outs) in msgs.chunks(4).zip(outs.chunks_mut(4)) {
+                    // AVX2 was checked to be supported by the CPU in `Hasher::new()`
+                    unsafe { self.hash_lanes_avx2(msgs, outs) };
+                }
+                return;
             }
         }
-        self.hash_portable(out, msg)
+        for (msg, out) in msgs.iter().zip(outs.iter_mut()) {
+            let mut st = self.start();
+            st.update(self, msg);
+            st.finalize(self, out);
+        }
     }
 
-    pub fn new(key: &[u8]) -> Hasher<'_> {
+    /// `backend` must be supported by the CPU
+    pub fn new(key: &[u8], backend: Backend) -> Hasher {
         assert_eq!(key.len(), NHPOLY_KEY_BYTES);
-        Hasher { key }
+        assert!(backend.is_supported());
+        let mut poly_key = [0u8; 16];
+        poly_key.copy_from_slice(&key[0..16]);
+        let update_blocks: UpdateBlocksFn = match backend {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => State::update_blocks_avx2,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse2 => State::update_blocks_sse2,
+            _ => State::update_blocks_portable,
+        };
+        // The portable backend only uses portable code, even for Poly1305
+        let update_poly: UpdatePolyFn = match backend {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse2 | Backend::Avx2 if poly1305::is_bmi2_supported() => {
+                poly1305::Hasher::update_bmi2
+            }
+            _ => poly1305::Hasher::update,
+        };
+        let poly_key = poly1305::Key::new(poly_key);
+        Hasher {
+            #[cfg(target_arch = "x86_64")]
+            poly_powers: poly1305::Powers::new(&poly_key),
+            poly_key,
+            st_nh: nh::Hasher::new(&key[16..]),
+            backend,
+            update_blocks,
+            update_poly,
+        }
+    }
+}
+
+/// Incremental NH+Poly1305 state.
+///
+/// Full NH blocks are compressed as soon as they are available; a partial
+/// block is kept in `pending` until more data arrives or the state is finalized.
+#[derive(Clone)]
+pub struct State {
+    st_poly: poly1305::Hasher,
+    nh_out: [u8; NHPOLY_NH_OUT_BYTES],
+    nh_out_len: usize,
+    pending: [u8; nh::NH_MESSAGE_BYTES],
+    pending_len: usize,
+}
+
+impl State {
+    pub fn update(&mut self, hasher: &Hasher, msg: &[u8]) {
+        let mut msg = msg;
+        if self.pending_len > 0 {
+            let n = (nh::NH_MESSAGE_BYTES - self.pending_len).min(msg.len());
+            self.pending[self.pending_len..self.pending_len + n].copy_from_slice(&msg[..n]);
+            self.pending_len += n;
+            msg = &msg[n..];
+            if self.pending_len < nh::NH_MESSAGE_BYTES {
+                return;
+            }
+            let pending = self.pending;
+            self.update_blocks(hasher, &pending);
+            self.pending_len = 0;
+        }
+        let full_len = msg.len() - msg.len() % nh::NH_MESSAGE_BYTES;
+        if full_len > 0 {
+            self.update_blocks(hasher, &msg[..full_len]);
+        }
+        let rest = &msg[full_len..];
+        self.pending[..rest.len()].copy_from_slice(rest);
+        self.pending_len = rest.len();
+    }
+
+    fn update_blocks(&mut self, hasher: &Hasher, blocks: &[u8]) {
+        debug_assert_eq!(blocks.len() % nh::NH_MESSAGE_BYTES, 0);
+        // The backend was checked to be supported by the CPU in `Hasher::new()`
+        unsafe { (hasher.update_blocks)(self, hasher, blocks) }
+    }
+
+    pub fn finalize(mut self, hasher: &Hasher, out: &mut [u8; 16]) {
+        self.flush(hasher);
+        self.st_poly.finalize_noadd(out);
+    }
+
+    /// Hashes the pending data and NH outputs; no data can be added afterwards
+    fn flush(&mut self, hasher: &Hasher) {
+        let remaining = self.pending_len;
+        if remaining > 0 {
+            let mask = nh::NH_MESSAGE_UNIT - 1;
+            let padded_len = (remaining + mask) & !mask;
+            let padded = &mut self.pending[..padded_len];
+            padded[remaining..].fill(0);
+            let mut nh_out = [0u8; nh::NH_OUTPUT_BYTES];
+            hasher.st_nh.hash(&mut nh_out, padded);
+            self.nh_out_slot().copy_from_slice(&nh_out);
+            self.commit_nh_out(hasher);
+            self.pending_len = 0;
+        }
+        if self.nh_out_len > 0 {
+            let nh_out_len = self.nh_out_len;
+            self.update_poly(hasher, nh_out_len);
+            self.nh_out_len = 0;
+        }
+    }
+
+    /// Returns the Poly1305 accumulator limbs
+    #[cfg(feature = "alloc")]
+    pub fn poly_limbs(&self) -> [u64; 3] {
+        self.st_poly.limbs()
+    }
+
+    /// Returns the NH outputs that haven't been compressed yet
+    #[cfg(feature = "alloc")]
+    pub fn nh_out(&self) -> &[u8] {
+        &self.nh_out[..self.nh_out_len]
+    }
+
+    /// Returns the data of the current partial block
+    #[cfg(feature = "alloc")]
+    pub fn pending(&self) -> &[u8] {
+        &self.pending[..self.pending_len]
+    }
+
+    /// Returns the buffer for the next NH output
+    #[inline(always)]
+    fn nh_out_slot(&mut self) -> &mut [u8; nh::NH_OUTPUT_BYTES] {
+        let slot = &mut self.nh_out[self.nh_out_len..self.nh_out_len + nh::NH_OUTPUT_BYTES];
+        slot.try_into().unwrap()
+    }
+
+    /// Records a new NH output, and returns `true` if a full batch is available
+    #[inline(always)]
+    fn push_nh_out(&mut self) -> bool {
+        self.nh_out_len += nh::NH_OUTPUT_BYTES;
+        self.nh_out_len == NHPOLY_NH_OUT_BYTES
+    }
+
+    /// Records a new NH output, and compresses the outputs once a full batch is available
+    #[inline(always)]
+    fn commit_nh_out(&mut self, hasher: &Hasher) {
+        if self.push_nh_out() {
+            self.update_poly(hasher, NHPOLY_NH_OUT_BYTES);
+            self.nh_out_len = 0;
+        }
+    }
+
+    /// Compresses the first `len` bytes of buffered NH outputs
+    #[inline(always)]
+    fn update_poly(&mut self, hasher: &Hasher, len: usize) {
+        // BMI2 and ADX were checked to be supported by the CPU in `Hasher::new()`
+        unsafe { (hasher.update_poly)(&mut self.st_poly, &self.nh_out[..len]) }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for State {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.nh_out);
     }
 }
@@ -1,6 +1,8 @@
 #[cfg(target_arch = "x86_64")]
 mod avx2;
 mod portable;
+#[cfg(target_arch = "x86_64")]
+mod sse2;
 
 use byteorder::{ByteOrder, LittleEndian};
 
@@ -14,6 +16,7 @@ pub const NH_KEYS_PER_MESSAGE: usize = NH_MESSAGE_BYTES;
 pub const NH_KEY_BYTES_PER_MESSAGE: usize = 4 * NH_KEYS_PER_MESSAGE;
 pub const NH_OUTPUT_BYTES: usize = 8 * 4;
 
+#[derive(Clone)]
 #[repr(align(32))]
 pub struct Hasher {
     key: [u32; NH_KEYS_PER_MESSAGE],
@@ -30,12 +33,19 @@ impl Hasher {
     }
 }
 
+#[cfg(feature = "zeroize")]
+impl Drop for Hasher {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.key);
+    }
+}
+
 #[test]
 fn basic_small() {
     let key = vec![1; NH_KEY_BYTES_PER_MESSAGE];
     let h = Hasher::new(&key);
     let msg = vec![42; 64];
-    let mut out = Vec::new();
+    let mut out = [0u8; NH_OUTPUT_BYTES];
     h.hash(&mut out, &msg);
     assert_eq!(
         out,
@@ -49,16 +59,66 @@ fn basic_small() {
 #[cfg(target_arch = "x86_64")]
 #[test]
 fn test_avx2() {
-    use rand::{thread_rng, RngCore};
+    use rand::{rng, RngCore};
 
     let mut key = vec![1; NH_KEY_BYTES_PER_MESSAGE];
-    thread_rng().fill_bytes(&mut key);
+    rng().fill_bytes(&mut key);
     let h = Hasher::new(&key);
     let mut msg = vec![0; 256];
-    thread_rng().fill_bytes(&mut msg);
-    let mut out = Vec::new();
+    rng().fill_bytes(&mut msg);
+    let mut out = [0u8; NH_OUTPUT_BYTES];
     h.hash(&mut out, &msg);
-    let mut out_avx2 = Vec::new();
+    let mut out_avx2 = [0u8; NH_OUTPUT_BYTES];
     unsafe { h.hash_avx2(&mut out_avx2, &msg) };
     assert_eq!(out, out_avx2);
 }
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn test_sse2() {
+    use rand::{rng, RngCore};
+
+    let mut key = vec![1; NH_KEY_BYTES_PER_MESSAGE];
+    rng().fill_bytes(&mut key);
+    let h = Hasher::new(&key);
+    let mut msg = vec![0; NH_MESSAGE_BYTES];
+    rng().fill_bytes(&mut msg);
+    for len in (0..=NH_MESSAGE_BYTES).step_by(NH_MESSAGE_UNIT) {
+        let mut out = [0u8; NH_OUTPUT_BYTES];
+        h.hash(&mut out, &msg[..len]);
+        let mut out_sse2 = [0u8; NH_OUTPUT_BYTES];
+        unsafe { h.hash_sse2(&mut out_sse2, &msg[..len]) };
+        assert_eq!(out, out_sse2);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn test_avx2_x4() {
+    use rand::{rng, RngCore};
+
+    if !is_x86_feature_detected!("avx2") {
+        return;
+    }
+    let mut key = vec![1; NH_KEY_BYTES_PER_MESSAGE];
+    rng().fill_bytes(&mut key);
+    let h = Hasher::new(&key);
+    let mut msg = vec![0; NH_MESSAGE_BYTES * 4];
+    rng().fill_bytes(&mut msg);
+    let msgs: Vec<&[u8]> = msg.chunks(NH_MESSAGE_BYTES).collect();
+    for len in (0..=NH_MESSAGE_BYTES).step_by(32) {
+        let mut outs = [[0u8; NH_OUTPUT_BYTES]; 4];
+        for (out, msg) in outs.iter_mut().zip(msgs.iter()) {
+            h.hash(out, &msg[..len]);
+        }
+        let mut outs_x4 = [[0u8; NH_OUTPUT_BYTES]; 4];
+        let msgs_x4 = [
+            &msgs[0][..len],
+            &msgs[1][..len],
+            &msgs[2][..len],
+            &msgs[3][..len],
+        ];
+        unsafe { h.hash_avx2_x4(&mut outs_x4, msgs_x4) };
+        assert_eq!(outs, outs_x4);
+    }
+}
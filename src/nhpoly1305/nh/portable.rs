@@ -1,25 +1,21 @@
-use std::io::Cursor;
-
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{ByteOrder, LittleEndian};
 
 use super::*;
 
 impl Hasher {
     #[inline(always)]
-    pub(crate) fn hash(&self, out: &mut Vec<u8>, msg: &[u8]) {
-        let mut cursor = Cursor::new(msg);
-        let mut remaining = msg.len();
+    pub(crate) fn hash(&self, out: &mut [u8; NH_OUTPUT_BYTES], msg: &[u8]) {
         let (mut s0, mut s1, mut s2, mut s3) = (0u64, 0u64, 0u64, 0u64);
         let mut key_ = &self.key[..];
 
         debug_assert_eq!(NH_NUM_PASSES, 4);
-        debug_assert_eq!(remaining % NH_MESSAGE_UNIT, 0);
-        debug_assert!(key_.len() >= remaining / NH_MESSAGE_UNIT * 16);
-        while remaining > 0 {
-            let m0 = cursor.read_u32::<LittleEndian>().unwrap();
-            let m1 = cursor.read_u32::<LittleEndian>().unwrap();
-            let m2 = cursor.read_u32::<LittleEndian>().unwrap();
-            let m3 = cursor.read_u32::<LittleEndian>().unwrap();
+        debug_assert_eq!(msg.len() % NH_MESSAGE_UNIT, 0);
+        debug_assert!(key_.len() >= msg.len() / NH_MESSAGE_UNIT * 16);
+        for unit in msg.chunks_exact(NH_MESSAGE_UNIT) {
+            let m0 = LittleEndian::read_u32(&unit[0..4]);
+            let m1 = LittleEndian::read_u32(&unit[4..8]);
+            let m2 = LittleEndian::read_u32(&unit[8..12]);
+            let m3 = LittleEndian::read_u32(&unit[12..16]);
 
             s0 = s0.wrapping_add(
                 ((m0.wrapping_add(key_[0])) as u64).wrapping_mul(m2.wrapping_add(key_[2]) as u64),
@@ -47,11 +43,10 @@ impl Hasher {
             );
 
             key_ = &key_[NH_MESSAGE_UNIT / 4..];
-            remaining -= NH_MESSAGE_UNIT;
         }
-        out.write_u64::<LittleEndian>(s0).unwrap();
-        out.write_u64::<LittleEndian>(s1).unwrap();
-        out.write_u64::<LittleEndian>(s2).unwrap();
-        out.write_u64::<LittleEndian>(s3).unwrap();
+        LittleEndian::write_u64(&mut out[0..8], s0);
+        LittleEndian::write_u64(&mut out[8..16], s1);
+        LittleEndian::write_u64(&mut out[16..24], s2);
+        LittleEndian::write_u64(&mut out[24..32], s3);
     }
 }
@@ -0,0 +1,61 @@
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+use super::*;
+
+impl Hasher {
+    #[allow(clippy::cast_ptr_alignment)]
+    #[target_feature(enable = "sse2")]
+    #[inline]
+    pub(crate) unsafe fn hash_sse2(&self, out: &mut [u8; NH_OUTPUT_BYTES], msg: &[u8]) {
+        let key_ = &self.key[..];
+        debug_assert_eq!(msg.len() % NH_MESSAGE_UNIT, 0);
+        debug_assert!(key_.len() >= msg.len() / NH_MESSAGE_UNIT * 4 + 12);
+        let mut k0 = _mm_loadu_si128(key_.as_ptr().add(0) as *const __m128i);
+        let mut k1 = _mm_loadu_si128(key_.as_ptr().add(4) as *const __m128i);
+        let mut k2 = _mm_loadu_si128(key_.as_ptr().add(8) as *const __m128i);
+        let (mut sums0, mut sums1, mut sums2, mut sums3) = (
+            _mm_setzero_si128(),
+            _mm_setzero_si128(),
+            _mm_setzero_si128(),
+            _mm_setzero_si128(),
+        );
+        for (i, unit) in msg.chunks_exact(NH_MESSAGE_UNIT).enumerate() {
+            let m = _mm_loadu_si128(unit.as_ptr() as *const __m128i);
+            let k3 = _mm_loadu_si128(key_.as_ptr().add(i * 4 + 12) as *const __m128i);
+            let t0 = _mm_add_epi32(k0, m);
+            let t1 = _mm_add_epi32(k1, m);
+            let t2 = _mm_add_epi32(k2, m);
+            let t3 = _mm_add_epi32(k3, m);
+            let t4 = _mm_shuffle_epi32(t0, 0x10);
+            let t0 = _mm_shuffle_epi32(t0, 0x32);
+            let t5 = _mm_shuffle_epi32(t1, 0x10);
+            let t1 = _mm_shuffle_epi32(t1, 0x32);
+            let t6 = _mm_shuffle_epi32(t2, 0x10);
+            let t2 = _mm_shuffle_epi32(t2, 0x32);
+            let t7 = _mm_shuffle_epi32(t3, 0x10);
+            let t3 = _mm_shuffle_epi32(t3, 0x32);
+            let t0 = _mm_mul_epu32(t0, t4);
+            let t1 = _mm_mul_epu32(t1, t5);
+            let t2 = _mm_mul_epu32(t2, t6);
+            let t3 = _mm_mul_epu32(t3, t7);
+            sums0 = _mm_add_epi64(sums0, t0);
+            sums1 = _mm_add_epi64(sums1, t1);
+            sums2 = _mm_add_epi64(sums2, t2);
+            sums3 = _mm_add_epi64(sums3, t3);
+            k0 = k1;
+            k1 = k2;
+            k2 = k3;
+        }
+
+        let t0 = _mm_unpacklo_epi64(sums0, sums1);
+        let t1 = _mm_unpackhi_epi64(sums0, sums1);
+        let t2 = _mm_unpacklo_epi64(sums2, sums3);
+        let t3 = _mm_unpackhi_epi64(sums2, sums3);
+        let t0 = _mm_add_epi64(t0, t1);
+        let t2 = _mm_add_epi64(t2, t3);
+
+        _mm_storeu_si128(out.as_mut_ptr() as *mut __m128i, t0);
+        _mm_storeu_si128(out.as_mut_ptr().add(16) as *mut __m128i, t2);
+    }
+}
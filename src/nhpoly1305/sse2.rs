@@ -0,0 +1,12 @@
+use super::*;
+
+impl State {
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "sse2")]
+    pub(crate) unsafe fn update_blocks_sse2(&mut self, hasher: &Hasher, blocks: &[u8]) {
+        for block in blocks.chunks_exact(nh::NH_MESSAGE_BYTES) {
+            hasher.st_nh.hash_sse2(self.nh_out_slot(), block);
+            self.commit_nh_out(hasher);
+        }
+    }
+}
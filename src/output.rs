@@ -0,0 +1,159 @@
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::hint::black_box;
+use core::str::FromStr;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use super::error::Error;
+use super::sthash::OUTPUT_BYTES;
+
+/// Minimum length of a truncated tag accepted by `Hasher::verify()`, in bytes
+pub const MIN_VERIFY_BYTES: usize = 16;
+
+/// An `OUTPUT_BYTES` hash
+///
+/// Equality and ordering are computed in constant time, so that outputs can
+/// be compared with untrusted values, and used as keys in ordered maps.
+#[derive(Clone, Copy)]
+pub struct Output([u8; OUTPUT_BYTES]);
+
+impl Output {
+    /// Returns the hash as a byte array
+    pub fn as_bytes(&self) -> &[u8; OUTPUT_BYTES] {
+        &self.0
+    }
+
+    /// Returns `true` if `self` and `other` are equal, in constant time
+    pub fn ct_eq(&self, other: &Output) -> bool {
+        ct_eq(&self.0, &other.0)
+    }
+
+    /// Returns `true` if `expected` is the hash, or a prefix of the hash of at
+    /// least `MIN_VERIFY_BYTES` bytes, in constant time
+    pub fn verify(&self, expected: &[u8]) -> bool {
+        let len = expected.len();
+        if !(MIN_VERIFY_BYTES..=OUTPUT_BYTES).contains(&len) {
+            return false;
+        }
+        ct_eq(&self.0[..len], expected)
+    }
+}
+
+pub(crate) fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    debug_assert_eq!(a.len(), b.len());
+    let d = a.iter().zip(b).fold(0u8, |d, (x, y)| d | (x ^ y));
+    black_box(d) == 0
+}
+
+fn ct_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    debug_assert_eq!(a.len(), b.len());
+    let (mut gt, mut eq) = (0u16, 1u16);
+    for (&x, &y) in a.iter().zip(b) {
+        let (x, y) = (x as u16, y as u16);
+        gt |= ((y.wrapping_sub(x) >> 8) & 1) & eq;
+        eq &= ((x ^ y).wrapping_sub(1) >> 8) & 1;
+    }
+    match black_box((gt << 1) | eq) {
+        0 => Ordering::Less,
+        1 => Ordering::Equal,
+        _ => Ordering::Greater,
+    }
+}
+
+impl PartialEq for Output {
+    fn eq(&self, other: &Output) -> bool {
+        self.ct_eq(other)
+    }
+}
+
+impl Eq for Output {}
+
+impl PartialOrd for Output {
+    fn partial_cmp(&self, other: &Output) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Output {
+    fn cmp(&self, other: &Output) -> Ordering {
+        ct_cmp(&self.0, &other.0)
+    }
+}
+
+impl Hash for Output {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl AsRef<[u8]> for Output {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<[u8; OUTPUT_BYTES]> for Output {
+    fn from(h: [u8; OUTPUT_BYTES]) -> Output {
+        Output(h)
+    }
+}
+
+impl From<Output> for [u8; OUTPUT_BYTES] {
+    fn from(h: Output) -> [u8; OUTPUT_BYTES] {
+        h.0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<Output> for Vec<u8> {
+    fn from(h: Output) -> Vec<u8> {
+        h.0.to_vec()
+    }
+}
+
+impl fmt::LowerHex for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(self, f)
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Output({:x})", self)
+    }
+}
+
+/// Parses a hexadecimal representation of a hash
+impl FromStr for Output {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Output, Error> {
+        let s = s.as_bytes();
+        if s.len() != OUTPUT_BYTES * 2 {
+            return Err(Error::InvalidEncoding);
+        }
+        let mut h = [0u8; OUTPUT_BYTES];
+        for (b, hex) in h.iter_mut().zip(s.chunks_exact(2)) {
+            let hi = (hex[0] as char)
+                .to_digit(16)
+                .ok_or(Error::InvalidEncoding)?;
+            let lo = (hex[1] as char)
+                .to_digit(16)
+                .ok_or(Error::InvalidEncoding)?;
+            *b = (hi << 4 | lo) as u8;
+        }
+        Ok(Output(h))
+    }
+}
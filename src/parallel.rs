@@ -0,0 +1,54 @@
+use rayon::prelude::*;
+
+use super::output::Output;
+use super::sthash::*;
+
+/// Default minimum amount of data hashed by each task of `par_hash_many()`, in bytes
+pub const PAR_MIN_WORK_BYTES: usize = 256 * 1024;
+
+impl Hasher {
+    /// Returns the hashes of independent messages, computed using the rayon
+    /// thread pool
+    ///
+    /// Hashes are returned in the same order as the messages.
+    /// Consecutive messages are grouped so that each task hashes at least
+    /// `PAR_MIN_WORK_BYTES` bytes.
+    pub fn par_hash_many(&self, msgs: &[&[u8]]) -> Vec<Output> {
+        self.par_hash_many_with_min_work(msgs, PAR_MIN_WORK_BYTES)
+    }
+
+    /// Same as `par_hash_many()`, with each task hashing at least `min_work_bytes` bytes
+    ///
+    /// Messages are never split: a message larger than `min_work_bytes` is
+    /// hashed by a single task. With `min_work_bytes` set to `0`, every
+    /// message can be hashed by a different task.
+    pub fn par_hash_many_with_min_work(
+        &self,
+        msgs: &[&[u8]],
+        min_work_bytes: usize,
+    ) -> Vec<Output> {
+        let mut outs = vec![[0u8; OUTPUT_BYTES]; msgs.len()];
+        let mut tasks = Vec::new();
+        let (mut msgs_left, mut outs_left) = (msgs, &mut outs[..]);
+        while !msgs_left.is_empty() {
+            let mut count = 0;
+            let mut work_bytes = 0;
+            while count < msgs_left.len() && (count == 0 || work_bytes < min_work_bytes) {
+                work_bytes += msgs_left[count].len();
+                count += 1;
+            }
+            let (task_msgs, rest_msgs) = msgs_left.split_at(count);
+            let (task_outs, rest_outs) = outs_left.split_at_mut(count);
+            tasks.push((task_msgs, task_outs));
+            msgs_left = rest_msgs;
+            outs_left = rest_outs;
+        }
+        // Cloning a `Hasher` only clones a reference to the expanded key
+        tasks
+            .into_par_iter()
+            .for_each_with(self.clone(), |hasher, (msgs, outs)| {
+                hasher.hash_batch_into(msgs, outs)
+            });
+        outs.into_iter().map(Output::from).collect()
+    }
+}
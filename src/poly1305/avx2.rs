@@ -0,0 +1,326 @@
+use core::arch::x86_64::*;
+
+use super::*;
+
+const MASK26: u64 = 0x3ffffff;
+
+/// r, r^2, r^3 and r^4, using 26-bit limbs
+#[derive(Clone, Debug)]
+pub struct Powers {
+    r: [[u64; 5]; 4],
+}
+
+impl Powers {
+    pub fn new(key: &Key) -> Powers {
+        let r1 = to_radix26(key.r0, key.r1, key.r2);
+        let r2 = mul26(&r1, &r1);
+        let r3 = mul26(&r2, &r1);
+        let r4 = mul26(&r3, &r1);
+        Powers {
+            r: [r1, r2, r3, r4],
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Powers {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.r);
+    }
+}
+
+/// Converts a value using 44-bit limbs (with `h0` and `h1` fully carried)
+/// into 26-bit limbs
+#[inline(always)]
+fn to_radix26(h0: u64, h1: u64, h2: u64) -> [u64; 5] {
+    [
+        h0 & MASK26,
+        ((h0 >> 26) | (h1 << 18)) & MASK26,
+        (h1 >> 8) & MASK26,
+        ((h1 >> 34) | (h2 << 10)) & MASK26,
+        h2 >> 16,
+    ]
+}
+
+/// Converts a value using 26-bit limbs into 44-bit limbs
+#[inline(always)]
+fn from_radix26(l: &[u64; 5]) -> (u64, u64, u64) {
+    let h0 = l[0] + (l[1] << 26);
+    let h1 = (h0 >> 44) + (l[2] << 8) + (l[3] << 34);
+    let h2 = (h1 >> 44) + (l[4] << 16);
+    (h0 & 0xfffffffffff, h1 & 0xfffffffffff, h2)
+}
+
+/// Carries a value using 26-bit limbs, so that all limbs but the second one
+/// are below 2^26
+#[inline(always)]
+fn carry26(d: &mut [u64; 5]) {
+    for i in 0..4 {
+        d[i + 1] += d[i] >> 26;
+        d[i] &= MASK26;
+    }
+    d[0] += (d[4] >> 26) * 5;
+    d[4] &= MASK26;
+    d[1] += d[0] >> 26;
+    d[0] &= MASK26;
+}
+
+/// Multiplies two values modulo 2^130-5, using 26-bit limbs
+fn mul26(a: &[u64; 5], b: &[u64; 5]) -> [u64; 5] {
+    let s: [u64; 5] = [b[0] * 5, b[1] * 5, b[2] * 5, b[3] * 5, b[4] * 5];
+    let mut d = [
+        a[0] * b[0] + a[1] * s[4] + a[2] * s[3] + a[3] * s[2] + a[4] * s[1],
+        a[0] * b[1] + a[1] * b[0] + a[2] * s[4] + a[3] * s[3] + a[4] * s[2],
+        a[0] * b[2] + a[1] * b[1] + a[2] * b[0] + a[3] * s[4] + a[4] * s[3],
+        a[0] * b[3] + a[1] * b[2] + a[2] * b[1] + a[3] * b[0] + a[4] * s[4],
+        a[0] * b[4] + a[1] * b[3] + a[2] * b[2] + a[3] * b[1] + a[4] * b[0],
+    ];
+    carry26(&mut d);
+    d
+}
+
+/// Multiplies each lane of `a` by the matching lane of `b` modulo 2^130-5
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn mul26x4(a: &[__m256i; 5], b: &[__m256i; 5], s: &[__m256i; 5]) -> [__m256i; 5] {
+    let m = |x, y| _mm256_mul_epu32(x, y);
+    let add = |x, y| _mm256_add_epi64(x, y);
+    let mut d = [
+        add(
+            add(
+                add(m(a[0], b[0]), m(a[1], s[4])),
+                add(m(a[2], s[3]), m(a[3], s[2])),
+            ),
+            m(a[4], s[1]),
+        ),
+        add(
+            add(
+                add(m(a[0], b[1]), m(a[1], b[0])),
+                add(m(a[2], s[4]), m(a[3], s[3])),
+            ),
+            m(a[4], s[2]),
+        ),
+        add(
+            add(
+                add(m(a[0], b[2]), m(a[1], b[1])),
+                add(m(a[2], b[0]), m(a[3], s[4])),
+            ),
+            m(a[4], s[3]),
+        ),
+        add(
+            add(
+                add(m(a[0], b[3]), m(a[1], b[2])),
+                add(m(a[2], b[1]), m(a[3], b[0])),
+            ),
+            m(a[4], s[4]),
+        ),
+        add(
+            add(
+                add(m(a[0], b[4]), m(a[1], b[3])),
+                add(m(a[2], b[2]), m(a[3], b[1])),
+            ),
+            m(a[4], b[0]),
+        ),
+    ];
+    let mask = _mm256_set1_epi64x(MASK26 as i64);
+    for i in 0..4 {
+        d[i + 1] = add(d[i + 1], _mm256_srli_epi64(d[i], 26));
+        d[i] = _mm256_and_si256(d[i], mask);
+    }
+    let c = _mm256_srli_epi64(d[4], 26);
+    d[4] = _mm256_and_si256(d[4], mask);
+    d[0] = add(d[0], add(c, _mm256_slli_epi64(c, 2)));
+    d[1] = add(d[1], _mm256_srli_epi64(d[0], 26));
+    d[0] = _mm256_and_si256(d[0], mask);
+    d
+}
+
+/// Loads 4 consecutive blocks, block `i` being stored in lane `i`,
+/// and adds `hibits` to the top limbs
+#[allow(clippy::cast_ptr_alignment)]
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn load_blocks(blocks: &[u8], hibits: __m256i) -> [__m256i; 5] {
+    debug_assert_eq!(blocks.len(), 64);
+    let x = _mm256_loadu_si256(blocks.as_ptr() as *const __m256i);
+    let y = _mm256_loadu_si256(blocks.as_ptr().add(32) as *const __m256i);
+    let t0 = _mm256_permute4x64_epi64(_mm256_unpacklo_epi64(x, y), 0xd8);
+    let t1 = _mm256_permute4x64_epi64(_mm256_unpackhi_epi64(x, y), 0xd8);
+    let mask = _mm256_set1_epi64x(MASK26 as i64);
+    [
+        _mm256_and_si256(t0, mask),
+        _mm256_and_si256(_mm256_srli_epi64(t0, 26), mask),
+        _mm256_and_si256(
+            _mm256_or_si256(_mm256_srli_epi64(t0, 52), _mm256_slli_epi64(t1, 12)),
+            mask,
+        ),
+        _mm256_and_si256(_mm256_srli_epi64(t1, 14), mask),
+        _mm256_or_si256(_mm256_srli_epi64(t1, 40), hibits),
+    ]
+}
+
+/// Copies the same value into all the lanes
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn splat(l: &[u64; 5]) -> [__m256i; 5] {
+    [
+        _mm256_set1_epi64x(l[0] as i64),
+        _mm256_set1_epi64x(l[1] as i64),
+        _mm256_set1_epi64x(l[2] as i64),
+        _mm256_set1_epi64x(l[3] as i64),
+        _mm256_set1_epi64x(l[4] as i64),
+    ]
+}
+
+/// Multiplies all the limbs by 5
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn times5(v: &[__m256i; 5]) -> [__m256i; 5] {
+    let t = |x| _mm256_add_epi64(x, _mm256_slli_epi64(x, 2));
+    [t(v[0]), t(v[1]), t(v[2]), t(v[3]), t(v[4])]
+}
+
+impl Hasher {
+    /// Same as `update()`, processing 4 blocks in parallel
+    ///
+    /// Each lane accumulates every fourth block using r^4, and the lanes are
+    /// combined at the end using r^4, r^3, r^2 and r.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn update_avx2(&mut self, powers: &Powers, msg: &[u8]) {
+        debug_assert_eq!(msg.len() % 16, 0);
+        let vectorized_len = msg.len() & !63;
+        if vectorized_len == 0 {
+            return self.update(msg);
+        }
+        let (vectorized, rest) = msg.split_at(vectorized_len);
+
+        let r4 = splat(&powers.r[3]);
+        let s4 = times5(&r4);
+        let hibits = _mm256_set1_epi64x(1 << 24);
+
+        // Carry h1 so that the state can be split into 26-bit limbs
+        let (h0, mut h1, mut h2) = (self.st.h0, self.st.h1, self.st.h2);
+        h2 += h1 >> 44;
+        h1 &= 0xfffffffffff;
+        let h = to_radix26(h0, h1, h2);
+
+        let mut blocks = vectorized.chunks_exact(64);
+        let mut acc = load_blocks(blocks.next().unwrap(), hibits);
+        for (i, limb) in acc.iter_mut().enumerate() {
+            *limb = _mm256_add_epi64(*limb, _mm256_set_epi64x(0, 0, 0, h[i] as i64));
+        }
+        for block in blocks {
+            let m = load_blocks(block, hibits);
+            acc = mul26x4(&acc, &r4, &s4);
+            for (a, m) in acc.iter_mut().zip(m.iter()) {
+                *a = _mm256_add_epi64(*a, *m);
+            }
+        }
+
+        let r = &powers.r;
+        let mut rs = [_mm256_setzero_si256(); 5];
+        for (i, limb) in rs.iter_mut().enumerate() {
+            *limb = _mm256_set_epi64x(
+                r[0][i] as i64,
+                r[1][i] as i64,
+                r[2][i] as i64,
+                r[3][i] as i64,
+            );
+        }
+        let acc = mul26x4(&acc, &rs, &times5(&rs));
+
+        let mut h = [0u64; 5];
+        for (i, limb) in acc.iter().enumerate() {
+            let mut lanes = [0u64; 4];
+            _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, *limb);
+            h[i] = lanes.iter().sum();
+        }
+        carry26(&mut h);
+        let (h0, h1, h2) = from_radix26(&h);
+        self.st.h0 = h0;
+        self.st.h1 = h1;
+        self.st.h2 = h2;
+
+        if !rest.is_empty() {
+            self.update(rest);
+        }
+    }
+}
+
+/// 4 independent Poly1305 states using the same key, one per lane
+///
+/// This is used to hash several messages at once.
+pub struct Lanes {
+    key: Key,
+    r: [__m256i; 5],
+    s: [__m256i; 5],
+    h: [__m256i; 5],
+}
+
+impl Lanes {
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn new(key: &Key, powers: &Powers) -> Lanes {
+        let r = splat(&powers.r[0]);
+        Lanes {
+            key: key.clone(),
+            r,
+            s: times5(&r),
+            h: [_mm256_setzero_si256(); 5],
+        }
+    }
+
+    /// Absorbs block `i` of `blocks` into lane `i`, for the lanes set in `active`
+    ///
+    /// Inactive lanes absorb the block without the high bit. If the block is
+    /// all zeros, this leaves a zero state unchanged, which allows messages of
+    /// different lengths to be aligned by their end.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn update(&mut self, blocks: &[u8; 64], active: [bool; 4]) {
+        let hibit = |i: usize| (active[i] as i64) << 24;
+        let hibits = _mm256_set_epi64x(hibit(3), hibit(2), hibit(1), hibit(0));
+        let m = load_blocks(blocks, hibits);
+        for (h, m) in self.h.iter_mut().zip(m.iter()) {
+            *h = _mm256_add_epi64(*h, *m);
+        }
+        self.h = mul26x4(&self.h, &self.r, &self.s);
+    }
+
+    /// Computes the Poly1305 output of each lane, without adding the final key
+    #[allow(clippy::cast_ptr_alignment)]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn finalize_noadd(&self, outs: &mut [[u8; 16]; 4]) {
+        let mut limbs = [[0u64; 4]; 5];
+        for (limb, h) in limbs.iter_mut().zip(self.h.iter()) {
+            _mm256_storeu_si256(limb.as_mut_ptr() as *mut __m256i, *h);
+        }
+        for (i, out) in outs.iter_mut().enumerate() {
+            let mut h = [
+                limbs[0][i],
+                limbs[1][i],
+                limbs[2][i],
+                limbs[3][i],
+                limbs[4][i],
+            ];
+            carry26(&mut h);
+            let (h0, h1, h2) = from_radix26(&h);
+            let mut st = Hasher::new(self.key.clone());
+            st.st = State { h0, h1, h2 };
+            st.finalize_noadd(out);
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Lanes {
+    fn drop(&mut self) {
+        // Vector registers can be zeroed without requiring any CPU feature
+        // SAFETY: the pointers come from mutable references, so they are valid and
+        // aligned, and an all-zero bit pattern is a valid `__m256i`.
+        unsafe {
+            core::ptr::write_volatile(&mut self.r, core::mem::zeroed());
+            core::ptr::write_volatile(&mut self.s, core::mem::zeroed());
+            core::ptr::write_volatile(&mut self.h, core::mem::zeroed());
+        }
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,80 @@
+use core::arch::x86_64::*;
+
+use super::*;
+
+/// Returns `true` if the CPU supports the BMI2 and ADX instructions
+pub fn is_supported() -> bool {
+    #[cfg(feature = "std")]
+    {
+        is_x86_feature_detected!("bmi2") && is_x86_feature_detected!("adx")
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        cfg!(all(target_feature = "bmi2", target_feature = "adx"))
+    }
+}
+
+impl Hasher {
+    /// Same as `update()`, using 64-bit limbs and the `mulx`, `adcx` and `adox` instructions
+    ///
+    /// `h` is kept partially reduced (`h < 2^131`) between blocks, and converted
+    /// from and to 44-bit limbs once per call.
+    #[target_feature(enable = "bmi2,adx")]
+    pub unsafe fn update_bmi2(&mut self, msg: &[u8]) {
+        debug_assert_eq!(msg.len() % 16, 0);
+        if msg.is_empty() {
+            return;
+        }
+        let (k0, k1, k2) = (self.key.r0, self.key.r1, self.key.r2);
+        let r0 = k0 | (k1 << 44);
+        let r1 = (k1 >> 20) | (k2 << 24);
+        let s1 = r1 + (r1 >> 2);
+
+        let (mut h1, mut h2) = (self.st.h1, self.st.h2);
+        h2 += h1 >> 44;
+        h1 &= 0xfffffffffff;
+        let mut h0 = self.st.h0 | (h1 << 44);
+        let mut h1 = (h1 >> 20) | (h2 << 24);
+        let mut h2 = h2 >> 40;
+
+        for block in msg.chunks_exact(16) {
+            let t0 = LittleEndian::read_u64(&block[0..8]);
+            let t1 = LittleEndian::read_u64(&block[8..16]);
+
+            // h += m
+            let c = _addcarryx_u64(0, h0, t0, &mut h0);
+            let c = _addcarryx_u64(c, h1, t1, &mut h1);
+            h2 += 1 + c as u64;
+
+            // d = h * r
+            let (mut d0_hi, mut t_hi, mut d1_hi, mut u_hi) = (0, 0, 0, 0);
+            let d0_lo = _mulx_u64(h0, r0, &mut d0_hi);
+            let t_lo = _mulx_u64(h1, s1, &mut t_hi);
+            let d1_lo = _mulx_u64(h0, r1, &mut d1_hi);
+            let u_lo = _mulx_u64(h1, r0, &mut u_hi);
+            let (mut d0, mut d1, mut d2) = (0, 0, 0);
+            let c = _addcarryx_u64(0, d0_lo, t_lo, &mut d0);
+            let c = _addcarryx_u64(c, d0_hi, t_hi, &mut d1);
+            d2 += c as u64;
+            let c = _addcarryx_u64(0, d1, d1_lo, &mut d1);
+            let c = _addcarryx_u64(c, d2, d1_hi, &mut d2);
+            debug_assert_eq!(c, 0);
+            let c = _addcarryx_u64(0, d1, u_lo, &mut d1);
+            let c = _addcarryx_u64(c, d2, u_hi, &mut d2);
+            debug_assert_eq!(c, 0);
+            let c = _addcarryx_u64(0, d1, h2 * s1, &mut d1);
+            d2 += c as u64 + h2 * r0;
+
+            // h = d mod 2^130-5, partially reduced
+            let c = (d2 & !3) + (d2 >> 2);
+            h2 = d2 & 3;
+            let c = _addcarryx_u64(0, d0, c, &mut h0);
+            let c = _addcarryx_u64(c, d1, 0, &mut h1);
+            h2 += c as u64;
+        }
+
+        self.st.h0 = h0 & 0xfffffffffff;
+        self.st.h1 = ((h0 >> 44) | (h1 << 20)) & 0xfffffffffff;
+        self.st.h2 = (h1 >> 24) | (h2 << 40);
+    }
+}
@@ -0,0 +1,413 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+#[cfg(target_arch = "x86_64")]
+mod avx2;
+#[cfg(target_arch = "x86_64")]
+mod bmi2;
+
+#[cfg(target_arch = "x86_64")]
+pub use self::avx2::{Lanes, Powers};
+#[cfg(target_arch = "x86_64")]
+pub use self::bmi2::is_supported as is_bmi2_supported;
+
+pub const POLY_KEY_BYTES: usize = 16;
+
+/// Size of a serialized accumulator, in bytes
+pub const ACCUMULATOR_BYTES: usize = 17;
+
+#[derive(Clone, Debug)]
+pub struct Key {
+    r0: u64,
+    r1: u64,
+    r2: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+struct State {
+    h0: u64,
+    h1: u64,
+    h2: u64,
+}
+
+impl Key {
+    pub fn new(key: [u8; 16]) -> Self {
+        let t0 = LittleEndian::read_u64(&key[0..8]);
+        let t1 = LittleEndian::read_u64(&key[8..16]);
+
+        let r0 = t0 & 0xffc0fffffff;
+        let r1 = ((t0 >> 44) | (t1 << 20)) & 0xfffffc0ffff;
+        let r2 = (t1 >> 24) & 0x00ffffffc0f;
+
+        Key { r0, r1, r2 }
+    }
+}
+
+impl State {
+    /// Returns `self * other` modulo 2^130-5, partially reduced
+    fn mul(&self, other: &State) -> State {
+        let (r0, r1, r2) = (other.h0, other.h1, other.h2);
+        let (h0, h1, h2) = (self.h0, self.h1, self.h2);
+        let s1 = r1 * (5 << 2);
+        let s2 = r2 * (5 << 2);
+
+        let d0 =
+            (h0 as u128) * (r0 as u128) + (h1 as u128) * (s2 as u128) + (h2 as u128) * (s1 as u128);
+        let mut d1 =
+            (h0 as u128) * (r1 as u128) + (h1 as u128) * (r0 as u128) + (h2 as u128) * (s2 as u128);
+        let mut d2 =
+            (h0 as u128) * (r2 as u128) + (h1 as u128) * (r1 as u128) + (h2 as u128) * (r0 as u128);
+
+        let c = d0 >> 44;
+        let mut h0 = d0 as u64 & 0xfffffffffff;
+        d1 += c;
+        let c = d1 >> 44;
+        let h1 = d1 as u64 & 0xfffffffffff;
+        d2 += c;
+        let c = d2 >> 42;
+        let h2 = d2 as u64 & 0x3ffffffffff;
+        h0 += c as u64 * 5;
+        let c = h0 >> 44;
+        h0 &= 0xfffffffffff;
+        State { h0, h1: h1 + c, h2 }
+    }
+
+    /// Returns `r^n` modulo 2^130-5
+    fn pow(key: &Key, mut n: u64) -> State {
+        let mut x = State {
+            h0: key.r0,
+            h1: key.r1,
+            h2: key.r2,
+        };
+        let mut acc = State {
+            h0: 1,
+            ..State::default()
+        };
+        while n > 0 {
+            if n & 1 != 0 {
+                acc = acc.mul(&x);
+            }
+            x = x.mul(&x);
+            n >>= 1;
+        }
+        acc
+    }
+}
+
+#[derive(Clone)]
+pub struct Hasher {
+    key: Key,
+    st: State,
+}
+
+impl Hasher {
+    pub fn update(&mut self, msg: &[u8]) {
+        let (r0, r1, r2) = (self.key.r0, self.key.r1, self.key.r2);
+        let (mut h0, mut h1, mut h2) = (self.st.h0, self.st.h1, self.st.h2);
+        let s1 = r1.wrapping_mul(5 << 2);
+        let s2 = r2.wrapping_mul(5 << 2);
+        let hibit = 1u64 << 40;
+        debug_assert_eq!(msg.len() % 16, 0);
+
+        for block in msg.chunks_exact(16) {
+            let t0 = LittleEndian::read_u64(&block[0..8]);
+            let t1 = LittleEndian::read_u64(&block[8..16]);
+
+            h0 += t0 & 0xfffffffffff;
+            h1 += ((t0 >> 44) | (t1 << 20)) & 0xfffffffffff;
+            h2 += ((t1 >> 24) & 0x3ffffffffff) | hibit;
+
+            let d0 = (h0 as u128).wrapping_mul(r0 as u128)
+                + (h1 as u128).wrapping_mul(s2 as u128)
+                + (h2 as u128).wrapping_mul(s1 as u128);
+            let mut d1 = (h0 as u128) * (r1 as u128)
+                + (h1 as u128).wrapping_mul(r0 as u128)
+                + (h2 as u128).wrapping_mul(s2 as u128);
+            let mut d2 = (h0 as u128) * (r2 as u128)
+                + (h1 as u128).wrapping_mul(r1 as u128)
+                + (h2 as u128).wrapping_mul(r0 as u128);
+
+            let c = d0 >> 44;
+            h0 = d0 as u64 & 0xfffffffffff;
+            d1 = d1.wrapping_add(c);
+            let c = d1 >> 44;
+            h1 = d1 as u64 & 0xfffffffffff;
+            d2 = d2.wrapping_add(c);
+            let c = d2 >> 42;
+            h2 = d2 as u64 & 0x3ffffffffff;
+            h0 = h0.wrapping_add((c as u64).wrapping_mul(5));
+            let c = h0 >> 44;
+            h0 &= 0xfffffffffff;
+            h1 = h1.wrapping_add(c);
+        }
+        self.st.h0 = h0;
+        self.st.h1 = h1;
+        self.st.h2 = h2;
+    }
+
+    /// Absorbs the blocks absorbed by `other`, as if they had been absorbed
+    /// by `self` after its own blocks
+    ///
+    /// `other` must use the same key, have started from an empty state, and
+    /// have absorbed `blocks` blocks.
+    pub fn combine(&mut self, other: &Hasher, blocks: u64) {
+        let mut st = self.st.mul(&State::pow(&self.key, blocks));
+        st.h0 += other.st.h0;
+        st.h1 += other.st.h1;
+        st.h2 += other.st.h2;
+        let c = st.h0 >> 44;
+        st.h0 &= 0xfffffffffff;
+        st.h1 += c;
+        self.st = st;
+    }
+
+    /// Returns the carried accumulator limbs, below 2^130 + 2^44
+    fn carry(&self) -> (u64, u64, u64) {
+        let (mut h0, mut h1, mut h2) = (self.st.h0, self.st.h1, self.st.h2);
+
+        // carry h
+        let c = h1 >> 44;
+        h1 &= 0xfffffffffff;
+        h2 += c;
+        let c = h2 >> 42;
+        h2 &= 0x3ffffffffff;
+        h0 += c * 5;
+        let c = h0 >> 44;
+        h0 &= 0xfffffffffff;
+        h1 += c;
+        let c = h1 >> 44;
+        h1 &= 0xfffffffffff;
+        h2 += c;
+        let c = h2 >> 42;
+        h2 &= 0x3ffffffffff;
+        h0 += c * 5;
+        let c = h0 >> 44;
+        h0 &= 0xfffffffffff;
+        h1 += c;
+        (h0, h1, h2)
+    }
+
+    pub fn finalize_noadd(&self, out: &mut [u8; 16]) {
+        let (mut h0, mut h1, mut h2) = self.carry();
+
+        // compute h + (-p)
+        let mut g0 = h0 + 5;
+        let c = g0 >> 44;
+        g0 &= 0xfffffffffff;
+        let mut g1 = h1 + c;
+        let c = g1 >> 44;
+        g1 &= 0xfffffffffff;
+        let mut g2 = (h2 + c).wrapping_sub(1u64 << 42);
+
+        // select h if h < p, or h + (-p) if h >= p
+        let mut mask = g2 >> 63;
+        g0 &= mask;
+        g1 &= mask;
+        g2 &= mask;
+        mask = !mask;
+        h0 = (h0 & mask) | g0;
+        h1 = (h1 & mask) | g1;
+        h2 = (h2 & mask) | g2;
+
+        // mac = h % (2^128)
+        h0 |= h1 << 44;
+        h1 = (h1 >> 20) | (h2 << 24);
+
+        LittleEndian::write_u64(&mut out[0..8], h0);
+        LittleEndian::write_u64(&mut out[8..16], h1);
+    }
+
+    /// Returns the accumulator, fully reduced and serialized
+    ///
+    /// Unlike `finalize_noadd()`, the returned value is always below p.
+    pub fn accumulator(&self) -> [u8; ACCUMULATOR_BYTES] {
+        let (mut h0, mut h1, mut h2) = self.carry();
+        let c = h1 >> 44;
+        h1 &= 0xfffffffffff;
+        h2 += c;
+
+        // compute h + (-p)
+        let mut g0 = h0 + 5;
+        let c = g0 >> 44;
+        g0 &= 0xfffffffffff;
+        let mut g1 = h1 + c;
+        let c = g1 >> 44;
+        g1 &= 0xfffffffffff;
+        let g2 = (h2 + c).wrapping_sub(1u64 << 42);
+
+        // select h if h < p, or h + (-p) if h >= p
+        let mask = (g2 >> 63).wrapping_sub(1);
+        h0 = (h0 & !mask) | (g0 & mask);
+        h1 = (h1 & !mask) | (g1 & mask);
+        h2 = (h2 & !mask) | (g2 & mask);
+
+        let mut out = [0u8; ACCUMULATOR_BYTES];
+        LittleEndian::write_u64(&mut out[0..8], h0 | (h1 << 44));
+        LittleEndian::write_u64(&mut out[8..16], (h1 >> 20) | (h2 << 24));
+        out[16] = (h2 >> 40) as u8;
+        out
+    }
+
+    /// Returns a new `Hasher` whose accumulator is `acc`
+    ///
+    /// `acc` must be below 2^130, which is always the case for values
+    /// returned by `accumulator()`.
+    pub fn with_accumulator(key: Key, acc: &[u8; ACCUMULATOR_BYTES]) -> Hasher {
+        debug_assert!(acc[16] < 4);
+        let t0 = LittleEndian::read_u64(&acc[0..8]);
+        let t1 = LittleEndian::read_u64(&acc[8..16]);
+        let st = State {
+            h0: t0 & 0xfffffffffff,
+            h1: ((t0 >> 44) | (t1 << 20)) & 0xfffffffffff,
+            h2: (t1 >> 24) | ((acc[16] as u64) << 40),
+        };
+        Hasher { key, st }
+    }
+
+    /// Returns the accumulator limbs, without any reduction
+    #[cfg(feature = "alloc")]
+    pub fn limbs(&self) -> [u64; 3] {
+        [self.st.h0, self.st.h1, self.st.h2]
+    }
+
+    /// Returns a new `Hasher` whose accumulator limbs are `limbs`
+    ///
+    /// `limbs` must have been returned by `limbs()`.
+    #[cfg(feature = "alloc")]
+    pub fn with_limbs(key: Key, limbs: [u64; 3]) -> Hasher {
+        let st = State {
+            h0: limbs[0],
+            h1: limbs[1],
+            h2: limbs[2],
+        };
+        Hasher { key, st }
+    }
+
+    #[allow(dead_code)]
+    pub fn hash(out: &mut [u8; 16], key: [u8; 16], msg: &[u8]) {
+        let mut h = Hasher::new(Key::new(key));
+        h.update(msg);
+        h.finalize_noadd(out);
+    }
+
+    pub fn new(key: Key) -> Hasher {
+        let st = State::default();
+        Hasher { key, st }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Key {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.r0);
+        zeroize::Zeroize::zeroize(&mut self.r1);
+        zeroize::Zeroize::zeroize(&mut self.r2);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for State {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.h0);
+        zeroize::Zeroize::zeroize(&mut self.h1);
+        zeroize::Zeroize::zeroize(&mut self.h2);
+    }
+}
+
+#[test]
+fn combine() {
+    let key = [0xa5; 16];
+    let msg: Vec<u8> = (0..4096).map(|i| (i * 7) as u8).collect();
+    let mut out = [0u8; 16];
+    Hasher::hash(&mut out, key, &msg);
+    for &split in &[0, 16, 512, 2000, 4096] {
+        let mut h1 = Hasher::new(Key::new(key));
+        h1.update(&msg[..split]);
+        let mut h2 = Hasher::new(Key::new(key));
+        h2.update(&msg[split..]);
+        let h2 = Hasher::with_accumulator(Key::new(key), &h2.accumulator());
+        h1.combine(&h2, ((msg.len() - split) / 16) as u64);
+        let mut out_combined = [0u8; 16];
+        h1.finalize_noadd(&mut out_combined);
+        assert_eq!(out, out_combined);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn test_avx2() {
+    use rand::{rng, Rng, RngCore};
+
+    if !is_x86_feature_detected!("avx2") {
+        return;
+    }
+    let check = |key: [u8; 16], prefix: &[u8], msg: &[u8]| {
+        let mut h = Hasher::new(Key::new(key));
+        h.update(prefix);
+        h.update(msg);
+        let mut out = [0u8; 16];
+        h.finalize_noadd(&mut out);
+
+        let key = Key::new(key);
+        let powers = Powers::new(&key);
+        let mut h = Hasher::new(key);
+        h.update(prefix);
+        unsafe { h.update_avx2(&powers, msg) };
+        let mut out_avx2 = [0u8; 16];
+        h.finalize_noadd(&mut out_avx2);
+        assert_eq!(out, out_avx2);
+    };
+    let mut rng = rng();
+    for _ in 0..200 {
+        let mut key = [0u8; 16];
+        rng.fill_bytes(&mut key);
+        let mut prefix = vec![0u8; rng.random_range(0..4) * 16];
+        rng.fill_bytes(&mut prefix);
+        let mut msg = vec![0u8; rng.random_range(0..64) * 16];
+        rng.fill_bytes(&mut msg);
+        check(key, &prefix, &msg);
+    }
+    check([0xff; 16], &[0xff; 1024], &[0xff; 1024]);
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn test_bmi2() {
+    use rand::{rng, Rng, RngCore};
+
+    if !is_bmi2_supported() {
+        return;
+    }
+    let check = |key: [u8; 16], prefix: &[u8], msg: &[u8]| {
+        let mut h = Hasher::new(Key::new(key));
+        h.update(prefix);
+        h.update(msg);
+        let mut out = [0u8; 16];
+        h.finalize_noadd(&mut out);
+
+        let mut h = Hasher::new(Key::new(key));
+        h.update(prefix);
+        unsafe { h.update_bmi2(msg) };
+        let mut out_bmi2 = [0u8; 16];
+        h.finalize_noadd(&mut out_bmi2);
+        assert_eq!(out, out_bmi2);
+
+        // Scalar updates must also be able to resume from a BMI2 state
+        let mut h = Hasher::new(Key::new(key));
+        unsafe { h.update_bmi2(prefix) };
+        h.update(msg);
+        let mut out_mixed = [0u8; 16];
+        h.finalize_noadd(&mut out_mixed);
+        assert_eq!(out, out_mixed);
+    };
+    let mut rng = rng();
+    for _ in 0..200 {
+        let mut key = [0u8; 16];
+        rng.fill_bytes(&mut key);
+        let mut prefix = vec![0u8; rng.random_range(0..4) * 16];
+        rng.fill_bytes(&mut prefix);
+        let mut msg = vec![0u8; rng.random_range(0..64) * 16];
+        rng.fill_bytes(&mut msg);
+        check(key, &prefix, &msg);
+    }
+    check([0xff; 16], &[0xff; 1024], &[0xff; 1024]);
+}
@@ -0,0 +1,196 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::nhpoly1305;
+use super::output::Output;
+use super::sthash::*;
+
+/// Size of the buffer used to hash readers and files, in bytes
+pub const READ_BUFFER_BYTES: usize = 64 * nhpoly1305::NHPOLY_BLOCK_BYTES;
+
+/// Adds all the data from `reader` to `st`, returning the number of bytes read
+fn update_from_reader<R: Read>(st: &mut State, mut reader: R) -> io::Result<u64> {
+    let mut buf = vec![0u8; READ_BUFFER_BYTES];
+    let mut read_len = 0;
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(read_len),
+            Ok(len) => {
+                st.update(&buf[..len]);
+                read_len += len as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+impl Hasher {
+    /// Returns an `OUTPUT_BYTES` hash of all the data that can be read from `reader`
+    ///
+    /// Data is read using a fixed-size buffer, so that arbitrarily large
+    /// streams can be hashed in bounded memory.
+    pub fn hash_reader<R: Read>(&self, reader: R) -> io::Result<Output> {
+        let mut st = self.start();
+        update_from_reader(&mut st, reader)?;
+        Ok(st.finalize())
+    }
+
+    /// Returns an `OUTPUT_BYTES` hash of the content of the file at `path`
+    pub fn hash_file<P: AsRef<Path>>(&self, path: P) -> io::Result<Output> {
+        self.hash_reader(File::open(path)?)
+    }
+
+    /// Returns an `OUTPUT_BYTES` hash of `len` bytes of the file at `path`,
+    /// starting at `offset`
+    ///
+    /// The result is the same as the hash of that range as a standalone message.
+    /// An `UnexpectedEof` error is returned if the file is too short.
+    pub fn hash_file_range<P: AsRef<Path>>(
+        &self,
+        path: P,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<Output> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut st = self.start();
+        if update_from_reader(&mut st, file.take(len))? != len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "File is too short for the requested range",
+            ));
+        }
+        Ok(st.finalize())
+    }
+}
+
+/// A reader that hashes all the data it reads from an inner reader
+///
+/// Data is returned unchanged. Only the bytes actually returned by the inner
+/// reader are hashed, so short reads are handled transparently.
+pub struct HashingReader<R> {
+    inner: R,
+    st: State,
+    len: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    /// Creates a new `HashingReader` that reads from `inner`, and hashes the
+    /// data using `hasher`
+    pub fn new(hasher: &Hasher, inner: R) -> HashingReader<R> {
+        HashingReader {
+            inner,
+            st: hasher.start(),
+            len: 0,
+        }
+    }
+
+    /// Returns a reference to the inner reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner reader
+    ///
+    /// Data read directly through the inner reader is not hashed.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the inner reader, the hash of the data read so far, and its length
+    pub fn finish(self) -> (R, Output, u64) {
+        (self.inner, self.st.finalize(), self.len)
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.st.update(&buf[..len]);
+        self.len += len as u64;
+        Ok(len)
+    }
+}
+
+/// A writer that hashes all the data it writes to an inner writer
+///
+/// Data is written unchanged. Only the bytes accepted by the inner writer
+/// are hashed: after a short write or an error, the hash covers exactly
+/// the data that was actually written.
+pub struct HashingWriter<W> {
+    inner: W,
+    st: State,
+    len: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    /// Creates a new `HashingWriter` that writes to `inner`, and hashes the
+    /// data using `hasher`
+    pub fn new(hasher: &Hasher, inner: W) -> HashingWriter<W> {
+        HashingWriter {
+            inner,
+            st: hasher.start(),
+            len: 0,
+        }
+    }
+
+    /// Returns a reference to the inner writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner writer
+    ///
+    /// Data written directly through the inner writer is not hashed.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the inner writer, the hash of the data written so far, and its length
+    ///
+    /// The inner writer is not flushed: buffered writers should be flushed
+    /// by the caller, so that errors are not lost.
+    pub fn finish(self) -> (W, Output, u64) {
+        (self.inner, self.st.finalize(), self.len)
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.st.update(&buf[..len]);
+        self.len += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Hasher {
+    /// Returns an `OUTPUT_BYTES` hash of the content of the file at `path`,
+    /// using a memory mapping instead of reading the file into a buffer
+    ///
+    /// Pipes, devices and other files that cannot be mapped are hashed
+    /// using buffered reads instead, so the result is always the same as
+    /// the one of `hash_file()`.
+    /// The file must not be modified while it is being hashed.
+    pub fn hash_file_mmap<P: AsRef<Path>>(&self, path: P) -> io::Result<Output> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        if !metadata.is_file() || metadata.len() == 0 {
+            return self.hash_reader(file);
+        }
+        let mmap = match unsafe { memmap2::Mmap::map(&file) } {
+            Ok(mmap) => mmap,
+            Err(_) => return self.hash_reader(file),
+        };
+        #[cfg(unix)]
+        let _ = mmap.advise(memmap2::Advice::Sequential);
+        Ok(self.hash(&mmap))
+    }
+}
@@ -1,12 +1,22 @@
-use std::sync::Arc;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
 
 use byteorder::{ByteOrder, LittleEndian};
-use tiny_keccak::{CShake, Hasher as _, Kmac};
+use tiny_keccak::{CShake, Hasher as _, IntoXof, Kmac, KmacXof, Xof};
 
+use super::backend::{Backend, BACKEND_ENV_VAR};
+use super::error::Error;
 use super::nhpoly1305;
+use super::output::{self, Output};
 
 const KMAC_KEY_BYTES: usize = 32;
-const KEY_BYTES: usize = KMAC_KEY_BYTES + nhpoly1305::NHPOLY_KEY_BYTES;
+
+/// Expanded key size, in bytes
+pub const KEY_BYTES: usize = KMAC_KEY_BYTES + nhpoly1305::NHPOLY_KEY_BYTES;
+
+/// Key fingerprint size, in bytes
+pub const KEY_FINGERPRINT_BYTES: usize = 16;
 
 /// Hash output size, in bytes
 pub const OUTPUT_BYTES: usize = 32;
@@ -17,39 +27,311 @@ pub const SEED_BYTES: usize = 32;
 /// Minimum seed size, in bytes
 pub const MIN_SEED_BYTES: usize = 16;
 
+/// Block size, in bytes
+///
+/// Partial hashes can only be concatenated if the first one covers a
+/// multiple of this size.
+pub const BLOCK_BYTES: usize = nhpoly1305::NHPOLY_BLOCK_BYTES;
+
+/// Serialized partial hash size, in bytes
+pub const PARTIAL_BYTES: usize = nhpoly1305::NHPOLY_ACCUMULATOR_BYTES + 8 + 8;
+
+#[cfg(feature = "alloc")]
+const CHECKPOINT_MAGIC: &[u8; 4] = b"STHC";
+#[cfg(feature = "alloc")]
+const CHECKPOINT_VERSION: u8 = 1;
+#[cfg(feature = "alloc")]
+const CHECKPOINT_HEADER_BYTES: usize = 4 + 1 + 8 + 3 * 8 + 2 + 2;
+#[cfg(feature = "alloc")]
+const CHECKPOINT_TAG_BYTES: usize = 32;
+
+/// Maximum number of messages hashed at once by `hash_batch_into()`
+const BATCH_SIZE: usize = 16;
+
 /// A large secret key, derived from a secret seed
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Key(Vec<u8>);
+///
+/// Keys are compared in constant time, and only their fingerprint is
+/// printed by `Debug`.
+/// With the `zeroize` feature, the key is wiped from memory when dropped.
+/// Temporary copies of the keyed KMAC state, made every time a hash is
+/// computed, are not wiped.
+#[derive(Clone)]
+pub struct Key([u8; KEY_BYTES]);
 
+#[derive(Clone)]
 struct HashInner {
-    key: Key,
     st_kmac: Kmac,
+    #[cfg(feature = "alloc")]
+    st_kmac_checkpoint: Kmac,
+    st_nhpoly: nhpoly1305::Hasher,
+}
+
+impl HashInner {
+    /// Returns the KMAC state for a message of length `msg_len`,
+    /// whose NH+Poly1305 hash is `poly`
+    ///
+    /// The returned state is a copy of the keyed state. `Kmac` is consumed by
+    /// `finalize()` and doesn't implement `Zeroize`, so this copy is not wiped,
+    /// even with the `zeroize` feature.
+    fn kmac(&self, msg_len: u64, poly: &[u8; 16]) -> Kmac {
+        let mut msg_len_u8 = [0u8; 8];
+        LittleEndian::write_u64(&mut msg_len_u8, msg_len);
+
+        let mut st_kmac = self.st_kmac.clone();
+        st_kmac.update(&msg_len_u8);
+        st_kmac.update(poly);
+        st_kmac
+    }
+
+    /// Returns the KMAC state for the concatenation of `chunks`
+    ///
+    /// Unlike `Hasher::start()`, this doesn't clone the `Hasher`, so one-shot
+    /// hashes neither copy the key nor update a reference count.
+    fn hash_kmac<I>(&self, chunks: I) -> Kmac
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let mut st_nhpoly = self.st_nhpoly.start();
+        let mut msg_len = 0u64;
+        for chunk in chunks {
+            let chunk = chunk.as_ref();
+            st_nhpoly.update(&self.st_nhpoly, chunk);
+            msg_len += chunk.len() as u64;
+        }
+        let mut poly = [0u8; 16];
+        st_nhpoly.finalize(&self.st_nhpoly, &mut poly);
+        self.kmac(msg_len, &poly)
+    }
+
+    /// Returns the authentication tag of a serialized checkpoint
+    #[cfg(feature = "alloc")]
+    fn checkpoint_tag(&self, checkpoint: &[u8]) -> [u8; CHECKPOINT_TAG_BYTES] {
+        let mut st_kmac = self.st_kmac_checkpoint.clone();
+        st_kmac.update(checkpoint);
+        let mut tag = [0u8; CHECKPOINT_TAG_BYTES];
+        st_kmac.finalize(&mut tag);
+        tag
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for HashInner {
+    fn drop(&mut self) {
+        // `Kmac` doesn't implement `Zeroize`; replace the keyed state with an unkeyed one
+        // SAFETY: the pointers come from mutable references, so they are valid and
+        // aligned. `Kmac` only holds plain arrays and integers, so overwriting it
+        // without dropping the previous value doesn't leak anything.
+        unsafe { core::ptr::write_volatile(&mut self.st_kmac, Kmac::v128(&[], &[])) };
+        #[cfg(feature = "alloc")]
+        // SAFETY: same as above
+        unsafe {
+            core::ptr::write_volatile(&mut self.st_kmac_checkpoint, Kmac::v128(&[], &[]))
+        };
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[cfg(feature = "alloc")]
+type Shared<T> = alloc::sync::Arc<T>;
+
+#[cfg(not(feature = "alloc"))]
+type Shared<T> = Inline<T>;
+
+/// Without an allocator, every `Hasher` owns a copy of the expanded key
+#[cfg(not(feature = "alloc"))]
+#[derive(Clone)]
+struct Inline<T>(T);
+
+#[cfg(not(feature = "alloc"))]
+impl<T> Inline<T> {
+    fn new(t: T) -> Inline<T> {
+        Inline(t)
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl<T> core::ops::Deref for Inline<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
 }
 
 /// A `Hasher` can be reused to compute multiple hashes using the same key
 #[derive(Clone)]
 pub struct Hasher {
-    inner: Arc<HashInner>,
+    inner: Shared<HashInner>,
 }
 
 impl Hasher {
     /// Returns an `OUTPUT_BYTES` hash of the message
-    pub fn hash(&self, msg: &[u8]) -> Vec<u8> {
-        let nhpoly_key = &self.inner.key.0[32..];
-        debug_assert_eq!(nhpoly_key.len(), nhpoly1305::NHPOLY_KEY_BYTES);
-        let st_nhpoly = nhpoly1305::Hasher::new(nhpoly_key);
+    pub fn hash(&self, msg: &[u8]) -> Output {
+        let mut h = [0u8; OUTPUT_BYTES];
+        self.hash_into(msg, &mut h);
+        Output::from(h)
+    }
+
+    /// Computes an `OUTPUT_BYTES` hash of the message into `out`
+    ///
+    /// Unlike `hash()`, this function doesn't allocate any memory. It doesn't
+    /// update the reference count of the shared key either, so it can be
+    /// called concurrently from many threads without contention.
+    pub fn hash_into(&self, msg: &[u8], out: &mut [u8; OUTPUT_BYTES]) {
+        self.inner.hash_kmac([msg]).finalize(out)
+    }
+
+    /// Computes a hash of the message, whose length is the length of `out`
+    ///
+    /// The output length is part of the KMAC input, so hashes of different
+    /// lengths are unrelated: a short hash is not a prefix of a longer one.
+    /// With a 32 byte output, the result is the same as the one of `hash()`.
+    pub fn hash_with_len(&self, msg: &[u8], out: &mut [u8]) {
+        self.inner.hash_kmac([msg]).finalize(out)
+    }
+
+    /// Returns a reader for an output of arbitrary length, computed using KMACXOF
+    ///
+    /// The output is unrelated to the output of `hash()` and `hash_with_len()`.
+    pub fn hash_xof(&self, msg: &[u8]) -> OutputReader {
+        OutputReader(self.inner.hash_kmac([msg]).into_xof())
+    }
+
+    /// Returns an `OUTPUT_BYTES` hash of the concatenation of `chunks`
+    ///
+    /// Chunks don't have to be aligned to blocks: at most one block is
+    /// buffered when a block spans multiple chunks.
+    pub fn hash_iter<I>(&self, chunks: I) -> Output
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let mut h = [0u8; OUTPUT_BYTES];
+        self.inner.hash_kmac(chunks).finalize(&mut h);
+        Output::from(h)
+    }
+
+    /// Returns an `OUTPUT_BYTES` hash of the concatenation of `bufs`
+    ///
+    /// The result is the same as the one of `hash_iter()`.
+    #[cfg(feature = "std")]
+    pub fn hash_vectored(&self, bufs: &[io::IoSlice<'_>]) -> Output {
+        self.hash_iter(bufs.iter().map(|buf| &buf[..]))
+    }
+
+    /// Returns `true` if `expected` is the hash of the message
+    ///
+    /// `expected` can be truncated, down to `MIN_VERIFY_BYTES` bytes.
+    /// The comparison is done in constant time.
+    pub fn verify<T: AsRef<[u8]> + ?Sized>(&self, msg: &[u8], expected: &T) -> bool {
+        self.hash(msg).verify(expected.as_ref())
+    }
+
+    /// Returns the hashes of independent messages, in the same order
+    ///
+    /// The result is the same as calling `hash()` on each message, but with
+    /// the AVX2 backend, up to 4 messages are processed at once, which is
+    /// faster for batches of small messages.
+    #[cfg(feature = "alloc")]
+    pub fn hash_batch(&self, msgs: &[&[u8]]) -> alloc::vec::Vec<Output> {
+        let mut outs = alloc::vec![[0u8; OUTPUT_BYTES]; msgs.len()];
+        self.hash_batch_into(msgs, &mut outs);
+        outs.into_iter().map(Output::from).collect()
+    }
+
+    /// Computes the hashes of independent messages into `outs`
+    ///
+    /// Unlike `hash_batch()`, this function doesn't allocate any memory.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `outs` doesn't have the same length as `msgs`.
+    pub fn hash_batch_into(&self, msgs: &[&[u8]], outs: &mut [[u8; OUTPUT_BYTES]]) {
+        assert_eq!(msgs.len(), outs.len(), "Output count mismatch");
+        for (msgs, outs) in msgs.chunks(BATCH_SIZE).zip(outs.chunks_mut(BATCH_SIZE)) {
+            let mut polys = [[0u8; 16]; BATCH_SIZE];
+            let polys = &mut polys[..msgs.len()];
+            self.inner.st_nhpoly.hash_batch(msgs, polys);
+            for ((msg, poly), out) in msgs.iter().zip(polys.iter()).zip(outs.iter_mut()) {
+                self.inner.kmac(msg.len() as u64, poly).finalize(out);
+            }
+        }
+    }
+
+    /// Returns an `OUTPUT_BYTES` hash of the message, computed using up to
+    /// `threads` threads
+    ///
+    /// The message is split into ranges that are hashed independently, and the
+    /// partial results are combined so that the output is the same as the
+    /// one of `hash()`. Messages are split on 1 KiB boundaries, so short
+    /// messages use fewer threads.
+    #[cfg(feature = "std")]
+    pub fn hash_parallel(&self, msg: &[u8], threads: usize) -> Output {
         let mut poly = [0u8; 16];
-        st_nhpoly.hash(&mut poly, msg);
+        self.inner.st_nhpoly.hash_parallel(msg, threads, &mut poly);
+        let mut h = [0u8; OUTPUT_BYTES];
+        self.inner.kmac(msg.len() as u64, &poly).finalize(&mut h);
+        Output::from(h)
+    }
 
-        let mut msg_len_u8 = [0u8; 8];
-        LittleEndian::write_u64(&mut msg_len_u8, msg.len() as u64);
+    /// Returns the hash of the message, as well as the hashes of all its
+    /// prefixes whose length is a multiple of `interval`
+    ///
+    /// The `k`-th checkpoint hash is the same as `hash(&msg[..k * interval])`,
+    /// for `k` starting at 1, up to and including the entire message if its
+    /// length is a multiple of `interval`.
+    /// The message is only processed once: at every checkpoint, the
+    /// intermediate state is finalized without being consumed.
+    ///
+    /// This function panics if `interval` is `0`.
+    #[cfg(feature = "alloc")]
+    pub fn hash_with_checkpoints(
+        &self,
+        msg: &[u8],
+        interval: usize,
+    ) -> (Output, alloc::vec::Vec<Output>) {
+        assert!(interval > 0, "Checkpoint interval must not be zero");
+        let mut checkpoints = alloc::vec::Vec::with_capacity(msg.len() / interval);
+        let mut st = self.start();
+        for chunk in msg.chunks(interval) {
+            st.update(chunk);
+            if chunk.len() == interval {
+                checkpoints.push(st.finalize_copy());
+            }
+        }
+        (st.finalize(), checkpoints)
+    }
 
-        let mut st_kmac = self.inner.st_kmac.clone();
-        st_kmac.update(&msg_len_u8);
-        st_kmac.update(&poly);
-        let mut h = vec![0u8; 32];
-        st_kmac.finalize(&mut h);
-        h
+    /// Returns a partial hash of a part of a larger message
+    ///
+    /// Partial hashes of consecutive parts, possibly computed on different
+    /// machines, can be combined with `Partial::concat()`, and finalized into
+    /// the same hash as the one of `hash()` over the whole message.
+    /// All the parts but the last one must have a length that is a multiple
+    /// of `BLOCK_BYTES`.
+    pub fn hash_partial(&self, msg: &[u8]) -> Partial {
+        let mut acc = [0u8; nhpoly1305::NHPOLY_ACCUMULATOR_BYTES];
+        self.inner.st_nhpoly.hash_accumulator(msg, &mut acc);
+        Partial {
+            hasher: self.clone(),
+            acc,
+            len: msg.len() as u64,
+        }
+    }
+
+    /// Returns a new incremental hashing state
+    ///
+    /// Data can be added to the state in arbitrary chunks using `update()`.
+    /// `finalize()` returns the same hash as `hash()` over the concatenation
+    /// of all the chunks.
+    pub fn start(&self) -> State {
+        let st_nhpoly = self.inner.st_nhpoly.start();
+        State {
+            hasher: self.clone(),
+            st_nhpoly,
+            msg_len: 0,
+        }
     }
 
     /// Creates a new `Hasher` object using `key`
@@ -58,14 +340,400 @@ impl Hasher {
     /// of the hashes this `Hasher` will compute.
     /// The same key used with the same messages, but in different contexts will
     /// produce different outputs.
+    ///
+    /// The fastest implementation supported by the CPU is used, unless the
+    /// `STHASH_BACKEND` environment variable is set to the name of another
+    /// backend.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if that variable is set to an unknown backend, or
+    /// to a backend that is not supported by the CPU. Use `try_new()` to get
+    /// an error instead.
     pub fn new(key: Key, personalization: Option<&[u8]>) -> Hasher {
-        debug_assert_eq!(key.0.len(), KEY_BYTES);
+        let backend = Backend::default_backend().unwrap_or_else(|_| {
+            panic!(
+                "{} is not set to a backend supported by this CPU",
+                BACKEND_ENV_VAR
+            )
+        });
+        Hasher::new_with_backend(key, personalization, backend)
+    }
+
+    /// Creates a new `Hasher` object using `key` and a specific backend
+    ///
+    /// An error is returned if the backend is not supported by the CPU.
+    /// All the backends compute the same hashes.
+    pub fn with_backend(
+        key: Key,
+        personalization: Option<&[u8]>,
+        backend: Backend,
+    ) -> Result<Hasher, Error> {
+        if !backend.is_supported() {
+            return Err(Error::UnsupportedBackend);
+        }
+        Ok(Hasher::new_with_backend(key, personalization, backend))
+    }
+
+    /// Returns the backend used by this `Hasher`
+    pub fn backend(&self) -> Backend {
+        self.inner.st_nhpoly.backend()
+    }
+
+    fn new_with_backend(key: Key, personalization: Option<&[u8]>, backend: Backend) -> Hasher {
         let kmac_key = &key.0[..KMAC_KEY_BYTES];
         let st_kmac = Kmac::v128(kmac_key, personalization.unwrap_or_default());
+        // Checkpoints are authenticated using a different customization string,
+        // followed by the length-prefixed personalization
+        #[cfg(feature = "alloc")]
+        let st_kmac_checkpoint = {
+            let personalization = personalization.unwrap_or_default();
+            let mut personalization_len = [0u8; 8];
+            LittleEndian::write_u64(&mut personalization_len, personalization.len() as u64);
+            let mut st_kmac = Kmac::v128(kmac_key, b"sthash checkpoint");
+            st_kmac.update(&personalization_len);
+            st_kmac.update(personalization);
+            st_kmac
+        };
+        let st_nhpoly = nhpoly1305::Hasher::new(&key.0[KMAC_KEY_BYTES..], backend);
         Hasher {
-            inner: Arc::new(HashInner { key, st_kmac }),
+            inner: Shared::new(HashInner {
+                st_kmac,
+                #[cfg(feature = "alloc")]
+                st_kmac_checkpoint,
+                st_nhpoly,
+            }),
         }
     }
+
+    /// Creates a new `Hasher` object using `key`, or returns an error if the
+    /// default backend cannot be used
+    ///
+    /// See `new()` for a description of `personalization`.
+    /// Unlike `new()`, this function returns `Error::UnsupportedBackend`
+    /// instead of panicking if the `STHASH_BACKEND` environment variable is
+    /// set to an unknown backend, or to a backend that is not supported by
+    /// the CPU.
+    pub fn try_new(key: Key, personalization: Option<&[u8]>) -> Result<Hasher, Error> {
+        let backend = Backend::default_backend()?;
+        Ok(Hasher::new_with_backend(key, personalization, backend))
+    }
+}
+
+impl fmt::Debug for Hasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hasher")
+            .field("backend", &self.backend())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An incremental hashing state, created with `Hasher::start()`
+pub struct State {
+    hasher: Hasher,
+    st_nhpoly: nhpoly1305::State,
+    msg_len: u64,
+}
+
+impl State {
+    /// Adds `msg` to the data to be hashed
+    pub fn update(&mut self, msg: &[u8]) {
+        self.st_nhpoly.update(&self.hasher.inner.st_nhpoly, msg);
+        self.msg_len += msg.len() as u64;
+    }
+
+    /// Returns an `OUTPUT_BYTES` hash of all the data added to the state
+    pub fn finalize(self) -> Output {
+        let mut h = [0u8; OUTPUT_BYTES];
+        self.finalize_into(&mut h);
+        Output::from(h)
+    }
+
+    /// Computes an `OUTPUT_BYTES` hash of all the data added to the state into `out`
+    pub fn finalize_into(self, out: &mut [u8; OUTPUT_BYTES]) {
+        self.finalize_kmac().finalize(out);
+    }
+
+    /// Computes a hash of all the data added to the state, whose length is
+    /// the length of `out`
+    pub fn finalize_with_len(self, out: &mut [u8]) {
+        self.finalize_kmac().finalize(out);
+    }
+
+    /// Returns a reader for an output of arbitrary length, computed using KMACXOF
+    pub fn finalize_xof(self) -> OutputReader {
+        OutputReader(self.finalize_kmac().into_xof())
+    }
+
+    /// Returns a checkpoint, from which hashing can be resumed using `State::resume()`
+    ///
+    /// The format is:
+    ///
+    /// ```text
+    /// "STHC" || version (1) || le64(length) || le64(limbs[0..3]) ||
+    /// le16(|nh_out|) || le16(|pending|) || nh_out || pending || tag
+    /// ```
+    ///
+    /// where `nh_out` are the NH outputs that haven't been compressed yet,
+    /// `pending` is the data of the current partial block, and `tag` is a
+    /// 256-bit KMAC128 of the previous fields, keyed with the `Hasher` key.
+    ///
+    /// The checkpoint contains values derived from the key, as well as up to
+    /// 1 KiB of the data: it must be kept as secret as the data itself.
+    #[cfg(feature = "alloc")]
+    pub fn export_checkpoint(&self) -> alloc::vec::Vec<u8> {
+        let nh_out = self.st_nhpoly.nh_out();
+        let pending = self.st_nhpoly.pending();
+        let mut bytes = alloc::vec::Vec::with_capacity(
+            CHECKPOINT_HEADER_BYTES + nh_out.len() + pending.len() + CHECKPOINT_TAG_BYTES,
+        );
+        bytes.extend_from_slice(CHECKPOINT_MAGIC);
+        bytes.push(CHECKPOINT_VERSION);
+        let mut header = [0u8; CHECKPOINT_HEADER_BYTES - 5];
+        LittleEndian::write_u64(&mut header[0..8], self.msg_len);
+        LittleEndian::write_u64_into(&self.st_nhpoly.poly_limbs(), &mut header[8..32]);
+        LittleEndian::write_u16(&mut header[32..34], nh_out.len() as u16);
+        LittleEndian::write_u16(&mut header[34..36], pending.len() as u16);
+        bytes.extend_from_slice(&header);
+        bytes.extend_from_slice(nh_out);
+        bytes.extend_from_slice(pending);
+        let tag = self.hasher.inner.checkpoint_tag(&bytes);
+        bytes.extend_from_slice(&tag);
+        bytes
+    }
+
+    /// Resumes hashing from a checkpoint created with `export_checkpoint()`
+    ///
+    /// `hasher` must use the same key and personalization as the `Hasher`
+    /// the checkpoint was created with, but can use a different backend.
+    /// `Error::InvalidCheckpoint` is returned if the checkpoint was modified.
+    #[cfg(feature = "alloc")]
+    pub fn resume(hasher: &Hasher, checkpoint: &[u8]) -> Result<State, Error> {
+        if checkpoint.len() < CHECKPOINT_HEADER_BYTES + CHECKPOINT_TAG_BYTES
+            || &checkpoint[0..4] != CHECKPOINT_MAGIC
+        {
+            return Err(Error::InvalidEncoding);
+        }
+        if checkpoint[4] != CHECKPOINT_VERSION {
+            return Err(Error::UnsupportedVersion);
+        }
+        let (content, tag) = checkpoint.split_at(checkpoint.len() - CHECKPOINT_TAG_BYTES);
+        if !output::ct_eq(&hasher.inner.checkpoint_tag(content), tag) {
+            return Err(Error::InvalidCheckpoint);
+        }
+        let (header, data) = content.split_at(CHECKPOINT_HEADER_BYTES);
+        let msg_len = LittleEndian::read_u64(&header[5..13]);
+        let mut poly_limbs = [0u64; 3];
+        LittleEndian::read_u64_into(&header[13..37], &mut poly_limbs);
+        let nh_out_len = LittleEndian::read_u16(&header[37..39]) as usize;
+        let pending_len = LittleEndian::read_u16(&header[39..41]) as usize;
+        if data.len() != nh_out_len + pending_len
+            || pending_len as u64 != msg_len % BLOCK_BYTES as u64
+        {
+            return Err(Error::InvalidEncoding);
+        }
+        let (nh_out, pending) = data.split_at(nh_out_len);
+        let st_nhpoly = hasher
+            .inner
+            .st_nhpoly
+            .resume(poly_limbs, nh_out, pending)
+            .ok_or(Error::InvalidEncoding)?;
+        Ok(State {
+            hasher: hasher.clone(),
+            st_nhpoly,
+            msg_len,
+        })
+    }
+
+    /// Returns an `OUTPUT_BYTES` hash of all the data added so far, without
+    /// consuming the state
+    #[cfg(feature = "alloc")]
+    fn finalize_copy(&self) -> Output {
+        let mut poly = [0u8; 16];
+        self.st_nhpoly
+            .clone()
+            .finalize(&self.hasher.inner.st_nhpoly, &mut poly);
+        let mut h = [0u8; OUTPUT_BYTES];
+        self.hasher.inner.kmac(self.msg_len, &poly).finalize(&mut h);
+        Output::from(h)
+    }
+
+    fn finalize_kmac(self) -> Kmac {
+        let mut poly = [0u8; 16];
+        self.st_nhpoly
+            .finalize(&self.hasher.inner.st_nhpoly, &mut poly);
+        self.hasher.inner.kmac(self.msg_len, &poly)
+    }
+}
+
+/// Data written to a `State` is added to the hash, and writes never fail.
+#[cfg(feature = "std")]
+impl io::Write for State {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        let mut len = 0;
+        for buf in bufs {
+            self.update(buf);
+            len += buf.len();
+        }
+        Ok(len)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.update(buf);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A reader for a variable-length output, returned by `Hasher::hash_xof()`
+#[derive(Clone)]
+pub struct OutputReader(KmacXof);
+
+impl OutputReader {
+    /// Fills `out` with the next bytes of the output
+    pub fn fill(&mut self, out: &mut [u8]) {
+        self.0.squeeze(out)
+    }
+}
+
+#[cfg(feature = "std")]
+impl io::Read for OutputReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.fill(buf);
+        Ok(buf.len())
+    }
+}
+
+/// A partial hash of a part of a message, created with `Hasher::hash_partial()`
+///
+/// A partial hash contains the Poly1305 accumulator of the part, and must be
+/// kept secret: unlike a final hash, it leaks information about the key.
+/// Partial hashes that are combined must have been computed with the same key
+/// and personalization.
+#[derive(Clone)]
+pub struct Partial {
+    hasher: Hasher,
+    acc: [u8; nhpoly1305::NHPOLY_ACCUMULATOR_BYTES],
+    len: u64,
+}
+
+impl Partial {
+    /// Returns the partial hash of `a` followed by `b`
+    ///
+    /// An error is returned if `b` is not empty and the length of `a` is not
+    /// a multiple of `BLOCK_BYTES`, or if the total length doesn't fit in
+    /// 64 bits.
+    pub fn concat(a: &Partial, b: &Partial) -> Result<Partial, Error> {
+        if b.len == 0 {
+            return Ok(a.clone());
+        }
+        if !a.len.is_multiple_of(BLOCK_BYTES as u64) {
+            return Err(Error::UnalignedPartial);
+        }
+        let len = a.len.checked_add(b.len).ok_or(Error::MessageTooLong)?;
+        let mut acc = a.acc;
+        a.hasher
+            .inner
+            .st_nhpoly
+            .concat_accumulators(&mut acc, &b.acc, b.blocks());
+        Ok(Partial {
+            hasher: a.hasher.clone(),
+            acc,
+            len,
+        })
+    }
+
+    /// Returns the length of the data covered by the partial hash, in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the partial hash doesn't cover any data
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of blocks covered by the partial hash
+    pub fn blocks(&self) -> u64 {
+        self.len.div_ceil(BLOCK_BYTES as u64)
+    }
+
+    /// Returns the hash of the data covered by the partial hash
+    ///
+    /// The result is the same as the one of `Hasher::hash()`.
+    pub fn finalize(&self) -> Output {
+        let mut poly = [0u8; 16];
+        self.hasher
+            .inner
+            .st_nhpoly
+            .finalize_accumulator(&self.acc, &mut poly);
+        let mut h = [0u8; OUTPUT_BYTES];
+        self.hasher.inner.kmac(self.len, &poly).finalize(&mut h);
+        Output::from(h)
+    }
+
+    /// Returns the `PARTIAL_BYTES` representation of the partial hash
+    ///
+    /// This is the accumulator, followed by the number of blocks and the
+    /// length, encoded as little-endian 64-bit integers.
+    /// Like the partial hash itself, it must be kept secret.
+    pub fn to_bytes(&self) -> [u8; PARTIAL_BYTES] {
+        let mut bytes = [0u8; PARTIAL_BYTES];
+        let (acc, counts) = bytes.split_at_mut(nhpoly1305::NHPOLY_ACCUMULATOR_BYTES);
+        acc.copy_from_slice(&self.acc);
+        LittleEndian::write_u64(&mut counts[..8], self.blocks());
+        LittleEndian::write_u64(&mut counts[8..], self.len);
+        bytes
+    }
+
+    /// Loads a partial hash previously exported with `to_bytes()`
+    ///
+    /// `hasher` must be the `Hasher` the partial hash was computed with.
+    pub fn from_bytes(hasher: &Hasher, bytes: &[u8]) -> Result<Partial, Error> {
+        if bytes.len() != PARTIAL_BYTES {
+            return Err(Error::InvalidEncoding);
+        }
+        let (acc_bytes, counts) = bytes.split_at(nhpoly1305::NHPOLY_ACCUMULATOR_BYTES);
+        let mut acc = [0u8; nhpoly1305::NHPOLY_ACCUMULATOR_BYTES];
+        acc.copy_from_slice(acc_bytes);
+        let blocks = LittleEndian::read_u64(&counts[..8]);
+        let len = LittleEndian::read_u64(&counts[8..]);
+        // The accumulator is reduced modulo 2^130-5
+        if acc[nhpoly1305::NHPOLY_ACCUMULATOR_BYTES - 1] > 3
+            || blocks != len.div_ceil(BLOCK_BYTES as u64)
+        {
+            return Err(Error::InvalidEncoding);
+        }
+        Ok(Partial {
+            hasher: hasher.clone(),
+            acc,
+            len,
+        })
+    }
+}
+
+impl fmt::Debug for Partial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Partial")
+            .field("len", &self.len)
+            .field("blocks", &self.blocks())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Partial {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.acc);
+    }
 }
 
 impl Key {
@@ -75,14 +743,92 @@ impl Key {
     /// `personalization` is an optional context, that can be set to the
     /// application name. The same `seed` used in different contexts will
     /// produce different keys, hence different hashes.
+    ///
+    /// This function panics if the seed is shorter than `MIN_SEED_BYTES`.
     pub fn from_seed(seed: &[u8], personalization: Option<&[u8]>) -> Key {
         if seed.len() < MIN_SEED_BYTES {
             panic!("Seed is too short");
         }
+        Key::derive(seed, personalization)
+    }
+
+    /// Creates a new key from a secret `seed`, or returns an error if the
+    /// seed is too short or obviously not random
+    ///
+    /// Unlike `from_seed()`, this function also rejects degenerate seeds,
+    /// such as seeds made of a single repeated byte.
+    pub fn try_from_seed(seed: &[u8], personalization: Option<&[u8]>) -> Result<Key, Error> {
+        if seed.len() < MIN_SEED_BYTES {
+            return Err(Error::SeedTooShort);
+        }
+        if seed.iter().all(|&b| b == seed[0]) {
+            return Err(Error::WeakSeed);
+        }
+        Ok(Key::derive(seed, personalization))
+    }
+
+    fn derive(seed: &[u8], personalization: Option<&[u8]>) -> Key {
         let mut st_cshake = CShake::v128(b"sthash key", personalization.unwrap_or_default());
         st_cshake.update(seed);
-        let mut key = vec![0; KEY_BYTES];
+        let mut key = [0u8; KEY_BYTES];
         st_cshake.finalize(&mut key);
         Key(key)
     }
+
+    /// Creates a key from its `KEY_BYTES` representation
+    ///
+    /// This can be used to load a key previously exported with `as_bytes()`,
+    /// or produced by another STHash implementation.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Key, Error> {
+        if bytes.len() != KEY_BYTES {
+            return Err(Error::InvalidKeyLength);
+        }
+        let mut key = [0u8; KEY_BYTES];
+        key.copy_from_slice(bytes);
+        Ok(Key(key))
+    }
+
+    /// Returns the `KEY_BYTES` representation of the key
+    ///
+    /// This is secret material, equivalent to the seed.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns a short, non-secret fingerprint of the key
+    ///
+    /// Fingerprints can be compared to check that different deployments
+    /// share the same key, without revealing the key.
+    pub fn fingerprint(&self) -> [u8; KEY_FINGERPRINT_BYTES] {
+        let mut st_cshake = CShake::v128(b"sthash key fingerprint", &[]);
+        st_cshake.update(&self.0);
+        let mut fingerprint = [0u8; KEY_FINGERPRINT_BYTES];
+        st_cshake.finalize(&mut fingerprint);
+        fingerprint
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Key) -> bool {
+        output::ct_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Key {}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Key {{ fingerprint: ")?;
+        for b in &self.fingerprint() {
+            write!(f, "{:02x}", b)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Key {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.0);
+    }
 }
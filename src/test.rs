@@ -1,5 +1,36 @@
+#![allow(clippy::needless_range_loop)]
+
+#[cfg(feature = "tokio")]
+use crate::async_io::*;
+use crate::backend::*;
+use crate::error::*;
+#[cfg(feature = "std")]
+use crate::keyfile::*;
+use crate::output::*;
+#[cfg(feature = "std")]
+use crate::reader::*;
 use crate::sthash::*;
 
+fn test_seed() -> [u8; SEED_BYTES] {
+    let mut seed = [0; SEED_BYTES];
+    for (i, b) in seed.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    seed
+}
+
+fn test_key() -> Key {
+    Key::try_from_seed(&test_seed(), Some(b"test suite")).unwrap()
+}
+
+fn test_hasher() -> Hasher {
+    Hasher::new(test_key(), None)
+}
+
+fn test_msg(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7) as u8).collect()
+}
+
 #[test]
 fn basic() {
     let mut seed = [0; SEED_BYTES];
@@ -12,8 +43,8 @@ fn basic() {
     let h2 = hasher.hash(b"test data 2");
     assert_ne!(h1, h2);
     assert_eq!(
-        h1,
-        [
+        h1.as_bytes(),
+        &[
             207, 49, 8, 127, 113, 64, 236, 115, 32, 134, 137, 211, 231, 179, 55, 152, 157, 237,
             108, 170, 124, 221, 19, 27, 204, 147, 234, 183, 207, 229, 205, 115
         ]
@@ -31,10 +62,663 @@ fn large() {
     let large = vec![0x42; 10_0000];
     let h1 = hasher.hash(&large);
     assert_eq!(
-        h1,
-        [
+        h1.as_bytes(),
+        &[
             110, 162, 21, 125, 173, 183, 249, 134, 212, 41, 152, 188, 190, 128, 190, 146, 78, 80,
             111, 186, 86, 150, 73, 137, 12, 42, 117, 217, 69, 154, 74, 231
         ]
     );
 }
+
+#[test]
+fn streaming() {
+    let hasher = test_hasher();
+    let msg = test_msg(40_000);
+    for &len in &[0, 1, 15, 16, 1023, 1024, 1025, 16_384, 16_401, 40_000] {
+        let msg = &msg[..len];
+        let h = hasher.hash(msg);
+        for &chunk_size in &[1, 13, 1000, 1024, 4096] {
+            let mut st = hasher.start();
+            for chunk in msg.chunks(chunk_size) {
+                st.update(chunk);
+            }
+            assert_eq!(st.finalize(), h);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn io_write() {
+    use std::io;
+
+    let hasher = test_hasher();
+    let msg = vec![0x69; 100_000];
+    let mut st = hasher.start();
+    let copied = io::copy(&mut &msg[..], &mut st).unwrap();
+    assert_eq!(copied, msg.len() as u64);
+    assert_eq!(st.finalize(), hasher.hash(&msg));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn reader_and_file() {
+    use std::io::{self, Read};
+
+    // A reader that never returns more than 1000 bytes at once
+    struct ShortReader<'t>(&'t [u8]);
+
+    impl Read for ShortReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(1000);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    let hasher = test_hasher();
+    let msg = test_msg(200_000);
+    let h = hasher.hash(&msg);
+    assert_eq!(hasher.hash_reader(ShortReader(&msg)).unwrap(), h);
+
+    let path = std::env::temp_dir().join(format!("sthash-test-{}", std::process::id()));
+    std::fs::write(&path, &msg).unwrap();
+    assert_eq!(hasher.hash_file(&path).unwrap(), h);
+    #[cfg(feature = "mmap")]
+    assert_eq!(hasher.hash_file_mmap(&path).unwrap(), h);
+    #[cfg(all(feature = "mmap", unix))]
+    assert_eq!(
+        hasher.hash_file_mmap("/dev/null").unwrap(),
+        hasher.hash(b"")
+    );
+    assert_eq!(
+        hasher.hash_file_range(&path, 1234, 100_000).unwrap(),
+        hasher.hash(&msg[1234..101_234])
+    );
+    assert_eq!(
+        hasher
+            .hash_file_range(&path, 150_000, 100_000)
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::UnexpectedEof
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn hash_into() {
+    let hasher = test_hasher();
+    for &len in &[0, 64, 1024, 20_000] {
+        let msg = vec![0x69; len];
+        let mut h = [0u8; OUTPUT_BYTES];
+        hasher.hash_into(&msg, &mut h);
+        assert_eq!(Output::from(h), hasher.hash(&msg));
+        let mut st = hasher.start();
+        st.update(&msg);
+        assert_eq!(Output::from(h), st.finalize());
+    }
+}
+
+#[test]
+fn output() {
+    let hasher = test_hasher();
+    let h1 = hasher.hash(b"test data 1");
+    let h2 = hasher.hash(b"test data 2");
+    assert!(h1.ct_eq(&h1));
+    assert!(!h1.ct_eq(&h2));
+    assert_eq!(h1.cmp(&h2), h1.as_bytes().cmp(h2.as_bytes()));
+    assert_eq!(h2.cmp(&h1), h2.as_bytes().cmp(h1.as_bytes()));
+    assert_eq!(h1.cmp(&h1), std::cmp::Ordering::Equal);
+
+    let hex = h1.to_string();
+    assert_eq!(hex.len(), OUTPUT_BYTES * 2);
+    assert_eq!(hex, format!("{:x}", h1));
+    assert_eq!(hex.parse::<Output>().unwrap(), h1);
+    assert_eq!(hex.to_uppercase().parse::<Output>().unwrap(), h1);
+    assert!(hex[1..].parse::<Output>().is_err());
+    assert!(hex.replace(&hex[..1], "g").parse::<Output>().is_err());
+
+    assert!(hasher.verify(b"test data 1", &h1));
+    assert!(hasher.verify(b"test data 1", &h1.as_bytes()[..MIN_VERIFY_BYTES]));
+    assert!(!hasher.verify(b"test data 1", &h1.as_bytes()[..MIN_VERIFY_BYTES - 1]));
+    assert!(!hasher.verify(b"test data 2", &h1));
+}
+
+#[test]
+fn variable_length() {
+    let hasher = test_hasher();
+    let msg = vec![0x69; 5000];
+
+    let mut h32 = [0u8; 32];
+    hasher.hash_with_len(&msg, &mut h32);
+    assert_eq!(&h32, hasher.hash(&msg).as_bytes());
+
+    let mut h16 = [0u8; 16];
+    hasher.hash_with_len(&msg, &mut h16);
+    let mut h64 = [0u8; 64];
+    hasher.hash_with_len(&msg, &mut h64);
+    assert_ne!(h16, h32[..16]);
+    assert_ne!(h32, h64[..32]);
+
+    let mut xof = hasher.hash_xof(&msg);
+    let mut x64 = [0u8; 64];
+    xof.fill(&mut x64[..10]);
+    xof.fill(&mut x64[10..]);
+    assert_ne!(x64, h64);
+    let mut x100 = [0u8; 100];
+    hasher.hash_xof(&msg).fill(&mut x100);
+    assert_eq!(x64, x100[..64]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn key_import_export() {
+    let key = test_key();
+    let key2 = Key::from_bytes(key.as_bytes()).unwrap();
+    assert_eq!(key2, key);
+    assert_eq!(key2.fingerprint(), key.fingerprint());
+    assert_ne!(
+        Key::from_seed(&[0x43; SEED_BYTES], None).fingerprint(),
+        key.fingerprint()
+    );
+    assert_eq!(
+        Key::from_bytes(&key.as_bytes()[1..]),
+        Err(Error::InvalidKeyLength)
+    );
+
+    let seed = test_seed();
+    for &personalization in &[None, Some(&b""[..]), Some(&b"test suite"[..])] {
+        let key_file = KeyFile::from_seed(&seed, personalization).unwrap();
+        assert_eq!(key_file.personalization(), personalization);
+        let bytes = key_file.to_bytes();
+        assert_eq!(KeyFile::from_bytes(&bytes).unwrap(), key_file);
+        let armored = key_file.to_armored();
+        assert!(armored.lines().all(|line| line.len() <= 64));
+        assert_eq!(KeyFile::from_armored(&armored).unwrap(), key_file);
+
+        let mut corrupted = bytes.clone();
+        corrupted[100] ^= 1;
+        assert_eq!(
+            KeyFile::from_bytes(&corrupted),
+            Err(Error::ChecksumMismatch)
+        );
+        corrupted[4] = 2;
+        assert_eq!(
+            KeyFile::from_bytes(&corrupted),
+            Err(Error::UnsupportedVersion)
+        );
+        assert_eq!(
+            KeyFile::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(Error::ChecksumMismatch)
+        );
+    }
+
+    assert_eq!(
+        KeyFile::from_seed(&[0x42; SEED_BYTES], None),
+        Err(Error::WeakSeed)
+    );
+    assert_eq!(
+        KeyFile::from_seed(&seed[..MIN_SEED_BYTES - 1], None),
+        Err(Error::SeedTooShort)
+    );
+    assert_eq!(
+        KeyFile::new(
+            key.clone(),
+            Some(&[0; KEY_FILE_MAX_PERSONALIZATION_BYTES + 1])
+        ),
+        Err(Error::PersonalizationTooLong)
+    );
+    let key_file =
+        KeyFile::new(key.clone(), Some(&[0; KEY_FILE_MAX_PERSONALIZATION_BYTES])).unwrap();
+    assert_eq!(KeyFile::from_bytes(&key_file.to_bytes()).unwrap(), key_file);
+
+    let key_file = KeyFile::new(key.clone(), Some(b"test suite")).unwrap();
+    let path = std::env::temp_dir().join(format!("sthash-key-{}", std::process::id()));
+    std::fs::write(&path, key_file.to_bytes()).unwrap();
+    assert_eq!(KeyFile::load(&path).unwrap(), key_file);
+    std::fs::write(&path, key_file.to_armored()).unwrap();
+    assert_eq!(KeyFile::load(&path).unwrap().into_key(), key);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn key_debug() {
+    let key = test_key();
+    let debug = format!("{:?}", key);
+    let fingerprint: String = key
+        .fingerprint()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    assert_eq!(debug, format!("Key {{ fingerprint: {} }}", fingerprint));
+    assert_ne!(key, Key::from_seed(&test_seed(), None));
+}
+
+#[test]
+fn fallible_constructors() {
+    let seed = test_seed();
+    let key = Key::try_from_seed(&seed, Some(b"test suite")).unwrap();
+    assert_eq!(key, Key::from_seed(&seed, Some(b"test suite")));
+    assert!(Hasher::try_new(key, None).is_ok());
+    assert_eq!(
+        Key::try_from_seed(&seed[..MIN_SEED_BYTES - 1], None),
+        Err(Error::SeedTooShort)
+    );
+    assert_eq!(
+        Key::try_from_seed(&[0; SEED_BYTES], None),
+        Err(Error::WeakSeed)
+    );
+    assert_eq!(
+        Key::try_from_seed(&[0x42; SEED_BYTES], None),
+        Err(Error::WeakSeed)
+    );
+}
+
+#[test]
+fn backends() {
+    let msg = test_msg(40_000);
+    let key = test_key();
+    let h = Hasher::with_backend(key.clone(), None, Backend::Portable)
+        .unwrap()
+        .hash(&msg);
+    for &backend in Backend::ALL {
+        assert_eq!(backend.name().parse::<Backend>().unwrap(), backend);
+        if !backend.is_supported() {
+            assert_eq!(
+                Hasher::with_backend(key.clone(), None, backend).unwrap_err(),
+                Error::UnsupportedBackend
+            );
+            continue;
+        }
+        let hasher = Hasher::with_backend(key.clone(), None, backend).unwrap();
+        assert_eq!(hasher.backend(), backend);
+        assert_eq!(hasher.hash(&msg), h);
+    }
+    assert!(detected_backend().is_supported());
+    assert_eq!("AVX2".parse::<Backend>().unwrap(), Backend::Avx2);
+    assert!("none".parse::<Backend>().is_err());
+}
+
+#[test]
+fn backend_env_var() {
+    assert_eq!(Backend::default_backend_from(None), Ok(detected_backend()));
+    assert_eq!(
+        Backend::default_backend_from(Some(" ")),
+        Ok(detected_backend())
+    );
+    assert_eq!(
+        Backend::default_backend_from(Some("Portable")),
+        Ok(Backend::Portable)
+    );
+    assert_eq!(
+        Backend::default_backend_from(Some("sse")),
+        Err(Error::UnsupportedBackend)
+    );
+    for &backend in Backend::ALL {
+        assert_eq!(
+            Backend::default_backend_from(Some(backend.name())).is_ok(),
+            backend.is_supported()
+        );
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn try_new_invalid_backend_env_var() {
+    // The environment is only modified in a child process, that runs this test again
+    const CHILD_ENV_VAR: &str = "STHASH_TEST_CHILD";
+    if std::env::var_os(CHILD_ENV_VAR).is_some() {
+        let key = test_key();
+        assert_eq!(
+            Hasher::try_new(key, None).unwrap_err(),
+            Error::UnsupportedBackend
+        );
+        return;
+    }
+    let status = std::process::Command::new(std::env::current_exe().unwrap())
+        .args([
+            "--exact",
+            "test::try_new_invalid_backend_env_var",
+            "--quiet",
+        ])
+        .env(CHILD_ENV_VAR, "1")
+        .env(BACKEND_ENV_VAR, "avx512")
+        .stdout(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn batch() {
+    let data = test_msg(40_000);
+    let lens = [
+        0, 1, 16, 1023, 1024, 1025, 4096, 4097, 9000, 16384, 40_000, 7, 2048, 0, 15_000, 333, 1024,
+        31_000,
+    ];
+    let msgs: Vec<&[u8]> = lens
+        .iter()
+        .enumerate()
+        .map(|(i, &len)| &data[i..][..len.min(data.len() - i)])
+        .collect();
+    let key = test_key();
+    for &backend in Backend::ALL {
+        if !backend.is_supported() {
+            continue;
+        }
+        let hasher = Hasher::with_backend(key.clone(), Some(b"batch"), backend).unwrap();
+        let expected: Vec<Output> = msgs.iter().map(|msg| hasher.hash(msg)).collect();
+        assert_eq!(hasher.hash_batch(&msgs), expected);
+        for n in 0..6 {
+            let mut outs = vec![[0u8; OUTPUT_BYTES]; n];
+            hasher.hash_batch_into(&msgs[..n], &mut outs);
+            for (out, expected) in outs.iter().zip(expected.iter()) {
+                assert_eq!(&Output::from(*out), expected);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_hash_many() {
+    let data = test_msg(100_000);
+    let msgs: Vec<&[u8]> = (0..300).map(|i| &data[i..][..(i * 331) % 5000]).collect();
+    let hasher = test_hasher();
+    let expected: Vec<Output> = msgs.iter().map(|msg| hasher.hash(msg)).collect();
+    assert_eq!(hasher.par_hash_many(&msgs), expected);
+    for &min_work_bytes in &[0, 1, 4096, 100_000, usize::MAX] {
+        assert_eq!(
+            hasher.par_hash_many_with_min_work(&msgs, min_work_bytes),
+            expected
+        );
+    }
+    assert!(hasher.par_hash_many(&[]).is_empty());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn hash_parallel() {
+    let msg = test_msg(100_000);
+    let hasher = test_hasher();
+    for &len in &[0, 1, 1024, 1025, 10_000, 16_384, 100_000] {
+        let h = hasher.hash(&msg[..len]);
+        for &threads in &[0, 1, 2, 3, 7, 200] {
+            assert_eq!(hasher.hash_parallel(&msg[..len], threads), h);
+        }
+    }
+}
+
+#[test]
+fn partial() {
+    let msg = test_msg(21_000);
+    let hasher = test_hasher();
+    for &split in &[0, 1024, 2048, 16_384, 19_456] {
+        for &len in &[split, split + 1, split + 1024, 21_000] {
+            let (a, b) = msg[..len].split_at(split);
+            let partial =
+                Partial::concat(&hasher.hash_partial(a), &hasher.hash_partial(b)).unwrap();
+            assert_eq!(partial.len(), len as u64);
+            assert_eq!(partial.finalize(), hasher.hash(&msg[..len]));
+
+            let partial = Partial::from_bytes(&hasher, &partial.to_bytes()).unwrap();
+            assert_eq!(partial.finalize(), hasher.hash(&msg[..len]));
+        }
+    }
+
+    let a = hasher.hash_partial(&msg[..1000]);
+    let b = hasher.hash_partial(&msg[1000..]);
+    assert_eq!(
+        Partial::concat(&a, &b).unwrap_err(),
+        Error::UnalignedPartial
+    );
+    let empty = hasher.hash_partial(&[]);
+    assert!(empty.is_empty());
+    assert_eq!(
+        Partial::concat(&a, &empty).unwrap().finalize(),
+        a.finalize()
+    );
+    assert_eq!(empty.finalize(), hasher.hash(&[]));
+
+    let mut bytes = a.to_bytes();
+    bytes[PARTIAL_BYTES - 1] ^= 1;
+    assert!(Partial::from_bytes(&hasher, &bytes).is_err());
+    assert!(Partial::from_bytes(&hasher, &bytes[1..]).is_err());
+
+    // Untrusted partials can have lengths whose sum overflows
+    let len = u64::MAX - 1023;
+    let mut bytes = empty.to_bytes();
+    bytes[PARTIAL_BYTES - 16..PARTIAL_BYTES - 8].copy_from_slice(&(len / 1024).to_le_bytes());
+    bytes[PARTIAL_BYTES - 8..].copy_from_slice(&len.to_le_bytes());
+    let huge = Partial::from_bytes(&hasher, &bytes).unwrap();
+    assert_eq!(
+        Partial::concat(&huge, &huge).unwrap_err(),
+        Error::MessageTooLong
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn checkpoint() {
+    let msg = test_msg(40_000);
+    let key = test_key();
+    let hasher = Hasher::new(key.clone(), None);
+    let h = hasher.hash(&msg);
+    for &split in &[0, 1, 1024, 1500, 16_384, 17_000, 40_000] {
+        let mut st = hasher.start();
+        st.update(&msg[..split]);
+        let checkpoint = st.export_checkpoint();
+        for &backend in Backend::ALL.iter().filter(|backend| backend.is_supported()) {
+            let hasher = Hasher::with_backend(key.clone(), None, backend).unwrap();
+            let mut st = State::resume(&hasher, &checkpoint).unwrap();
+            st.update(&msg[split..]);
+            assert_eq!(st.finalize(), h);
+        }
+    }
+
+    let mut st = hasher.start();
+    st.update(&msg[..20_000]);
+    let checkpoint = st.export_checkpoint();
+    for i in 5..checkpoint.len() {
+        let mut tampered = checkpoint.clone();
+        tampered[i] ^= 1;
+        assert_eq!(
+            State::resume(&hasher, &tampered).err(),
+            Some(Error::InvalidCheckpoint)
+        );
+    }
+    let other = Hasher::new(key, Some(b"other"));
+    assert_eq!(
+        State::resume(&other, &checkpoint).err(),
+        Some(Error::InvalidCheckpoint)
+    );
+    assert!(State::resume(&hasher, &checkpoint[..checkpoint.len() - 1]).is_err());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn hash_with_checkpoints() {
+    let msg = test_msg(40_000);
+    let hasher = test_hasher();
+    for &len in &[0, 100, 10_000, 40_000] {
+        for &interval in &[1000, 1024, 5000, 16_384, 50_000] {
+            let (h, checkpoints) = hasher.hash_with_checkpoints(&msg[..len], interval);
+            assert_eq!(h, hasher.hash(&msg[..len]));
+            assert_eq!(checkpoints.len(), len / interval);
+            for (k, checkpoint) in checkpoints.iter().enumerate() {
+                assert_eq!(*checkpoint, hasher.hash(&msg[..(k + 1) * interval]));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn vectored() {
+    use std::io::{self, Write};
+
+    let msg = test_msg(10_000);
+    let hasher = test_hasher();
+    let h = hasher.hash(&msg);
+    let (header, rest) = msg.split_at(13);
+    let (body, trailer) = rest.split_at(5000);
+    assert_eq!(hasher.hash_iter([header, body, trailer]), h);
+    assert_eq!(hasher.hash_iter(msg.chunks(1)), h);
+    assert_eq!(hasher.hash_iter(msg.chunks(1500)), h);
+    assert_eq!(hasher.hash_iter(Vec::<Vec<u8>>::new()), hasher.hash(&[]));
+
+    let bufs = [
+        io::IoSlice::new(header),
+        io::IoSlice::new(&[]),
+        io::IoSlice::new(body),
+        io::IoSlice::new(trailer),
+    ];
+    assert_eq!(hasher.hash_vectored(&bufs), h);
+    let mut st = hasher.start();
+    assert_eq!(st.write_vectored(&bufs).unwrap(), msg.len());
+    assert_eq!(st.finalize(), h);
+
+    #[cfg(feature = "bytes")]
+    {
+        use bytes::{Buf, Bytes};
+
+        let buf = Bytes::copy_from_slice(header)
+            .chain(Bytes::copy_from_slice(body))
+            .chain(trailer);
+        assert_eq!(hasher.hash_buf(buf), h);
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_io() {
+    use core::pin::Pin;
+    use core::task::{Context, Poll};
+    use std::io;
+    use tokio::io::AsyncWriteExt;
+
+    struct Chunks(std::vec::IntoIter<io::Result<Vec<u8>>>);
+
+    impl futures_core::Stream for Chunks {
+        type Item = io::Result<Vec<u8>>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Ready(self.0.next())
+        }
+    }
+
+    let msg = test_msg(3_000_000);
+    let hasher = test_hasher();
+    let h = hasher.hash(&msg);
+
+    for &len in &[0, 1000, ASYNC_BLOCKING_BYTES, msg.len()] {
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        let write = async {
+            client.write_all(&msg[..len]).await.unwrap();
+            drop(client);
+        };
+        let (_, res) = tokio::join!(write, hasher.hash_async_reader(server));
+        assert_eq!(res.unwrap(), hasher.hash(&msg[..len]));
+    }
+
+    let mut st = hasher.start_async();
+    st.write_all(&msg[..100]).await.unwrap();
+    st.write_all(&msg[100..2_100_000]).await.unwrap();
+    st.write_all(&msg[2_100_000..]).await.unwrap();
+    st.shutdown().await.unwrap();
+    assert_eq!(st.finalize().await, h);
+
+    let chunks = vec![
+        Ok(msg[..10].to_vec()),
+        Ok(msg[10..2_000_000].to_vec()),
+        Ok(msg[2_000_000..].to_vec()),
+    ];
+    assert_eq!(
+        hasher
+            .hash_stream(Chunks(chunks.into_iter()))
+            .await
+            .unwrap(),
+        h
+    );
+    let chunks = vec![Ok(msg[..10].to_vec()), Err(io::ErrorKind::Other.into())];
+    assert!(hasher
+        .hash_stream(Chunks(chunks.into_iter()))
+        .await
+        .is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn hashing_reader_writer() {
+    use std::io::{self, Read, Write};
+
+    /// Accepts at most 7 bytes per write, and `limit` bytes in total
+    struct ShortWriter {
+        data: Vec<u8>,
+        limit: usize,
+    }
+
+    impl Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let len = buf.len().min(7).min(self.limit - self.data.len());
+            if len == 0 && !buf.is_empty() {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.data.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let msg = test_msg(10_000);
+    let hasher = test_hasher();
+
+    let mut reader = HashingReader::new(&hasher, (&msg[..5000]).chain(&msg[5000..]));
+    let mut read = Vec::new();
+    reader.read_to_end(&mut read).unwrap();
+    assert_eq!(read, msg);
+    let (_, h, len) = reader.finish();
+    assert_eq!((h, len), (hasher.hash(&msg), msg.len() as u64));
+
+    let mut writer = HashingWriter::new(
+        &hasher,
+        ShortWriter {
+            data: Vec::new(),
+            limit: 20_000,
+        },
+    );
+    writer.write_all(&msg).unwrap();
+    assert_eq!(writer.get_ref().data, msg);
+    writer.get_mut().limit = 30_000;
+    let (inner, h, len) = writer.finish();
+    assert_eq!((h, len), (hasher.hash(&msg), msg.len() as u64));
+    assert_eq!(inner.limit, 30_000);
+
+    let mut writer = HashingWriter::new(
+        &hasher,
+        ShortWriter {
+            data: Vec::new(),
+            limit: 3000,
+        },
+    );
+    assert!(writer.write_all(&msg).is_err());
+    let (inner, h, len) = writer.finish();
+    assert_eq!((h, len), (hasher.hash(&msg[..3000]), 3000));
+    assert_eq!(inner.data, msg[..3000]);
+
+    // The inner writer can be recovered and flushed after `finish()`
+    let buffered = io::BufWriter::new(ShortWriter {
+        data: Vec::new(),
+        limit: 20_000,
+    });
+    let mut writer = HashingWriter::new(&hasher, buffered);
+    writer.write_all(&msg).unwrap();
+    let (buffered, h, _) = writer.finish();
+    assert_eq!(h, hasher.hash(&msg));
+    assert_eq!(buffered.into_inner().ok().unwrap().data, msg);
+}